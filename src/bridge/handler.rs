@@ -11,6 +11,7 @@ use super::ui_commands::UiCommand;
 #[cfg(windows)]
 use super::BRIDGE;
 use crate::settings::SETTINGS;
+use crate::window::{WindowCommand, WINDOW_COMMANDS};
 
 #[derive(Clone)]
 pub struct NeovimHandler();
@@ -41,6 +42,11 @@ impl Handler for NeovimHandler {
             "neovide.unregister_right_click" => {
                 BRIDGE.queue_command(UiCommand::UnregisterRightClick);
             }
+            window_command if window_command.starts_with("neovide.") => {
+                if let Some(command) = WindowCommand::parse(window_command, &arguments) {
+                    WINDOW_COMMANDS.queue(command);
+                }
+            }
             _ => {}
         })
         .await
@@ -2,10 +2,14 @@
 #[cfg_attr(feature = "winit", path = "winit.rs")]
 mod qwerty;
 
-use log::{error, trace};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use log::{debug, error, trace};
 
 #[cfg(feature = "sdl2")]
-use skulpin::sdl2::keyboard::{Keycode, Mod};
+use skulpin::sdl2::keyboard::{Keycode, Mod, Scancode};
 
 #[cfg(feature = "winit")]
 use skulpin::winit::event::ModifiersState;
@@ -16,19 +20,86 @@ use crate::settings::{FromValue, Value, SETTINGS};
 
 use qwerty::*;
 
+lazy_static! {
+    // What each key last typed on the active layout, with and without shift
+    static ref LAYOUT_TEXT: Mutex<HashMap<(Keycode, bool), String>> = Mutex::new(HashMap::new());
+}
+
 pub fn unsupported_key<R>(keycode: Keycode) -> Option<R> {
     trace!("Unsupported key: {:?}", keycode);
     None
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeybindingError {
+    NoInput,
+    EmptyText,
+    ModifierOnly(Keycode),
+    UnsupportedKey(Keycode),
+}
+
+impl fmt::Display for KeybindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeybindingError::NoInput => write!(f, "no keycode or text received"),
+            KeybindingError::EmptyText => write!(f, "received empty text input"),
+            KeybindingError::ModifierOnly(keycode) => {
+                write!(
+                    f,
+                    "{:?} is a modifier and only applies to other keys",
+                    keycode
+                )
+            }
+            KeybindingError::UnsupportedKey(keycode) => {
+                write!(f, "{:?} is not mapped by the keyboard layout", keycode)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sdl2")]
+fn is_modifier_key(keycode: Keycode) -> bool {
+    matches!(
+        keycode,
+        Keycode::LShift
+            | Keycode::RShift
+            | Keycode::LCtrl
+            | Keycode::RCtrl
+            | Keycode::LAlt
+            | Keycode::RAlt
+            | Keycode::LGui
+            | Keycode::RGui
+            | Keycode::Mode
+    )
+}
+
+#[cfg(feature = "winit")]
+fn is_modifier_key(keycode: Keycode) -> bool {
+    matches!(
+        keycode,
+        Keycode::LShift
+            | Keycode::RShift
+            | Keycode::LControl
+            | Keycode::RControl
+            | Keycode::LAlt
+            | Keycode::RAlt
+            | Keycode::LWin
+            | Keycode::RWin
+    )
+}
+
 #[derive(Clone)]
 pub enum KeyboardLayout {
+    // Opt in. Keys held with ctrl or alt are named by what they type on the active layout, as
+    // far as it has been seen, falling back to qwerty.
+    System,
     Qwerty,
 }
 
 impl FromValue for KeyboardLayout {
     fn from_value(&mut self, value: Value) {
         match value.as_str() {
+            Some("system") => *self = KeyboardLayout::System,
             Some("qwerty") => *self = KeyboardLayout::Qwerty,
             _ => error!(
                 "keyboard_layout setting expected a known keyboard layout name, but received: {}",
@@ -41,22 +112,209 @@ impl FromValue for KeyboardLayout {
 impl From<KeyboardLayout> for Value {
     fn from(layout: KeyboardLayout) -> Self {
         match layout {
+            KeyboardLayout::System => "system".into(),
             KeyboardLayout::Qwerty => "qwerty".into(),
         }
     }
 }
 
+// Lock keys never reach the keybinding, and AltGr is left out by default since some systems
+// report it on every key press. Adding altgr to the mask makes it count as alt.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModifierMask {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+    pub altgr: bool,
+}
+
+impl Default for ModifierMask {
+    fn default() -> Self {
+        ModifierMask {
+            shift: true,
+            ctrl: true,
+            alt: true,
+            gui: true,
+            altgr: false,
+        }
+    }
+}
+
+impl FromValue for ModifierMask {
+    fn from_value(&mut self, value: Value) {
+        let names = match value.as_array() {
+            Some(names) => names,
+            None => {
+                error!(
+                    "keyboard_modifier_mask setting expected a list of modifier names, but received: {}",
+                    value
+                );
+                return;
+            }
+        };
+
+        let mut mask = ModifierMask {
+            shift: false,
+            ctrl: false,
+            alt: false,
+            gui: false,
+            altgr: false,
+        };
+        for name in names {
+            match name.as_str() {
+                Some("shift") => mask.shift = true,
+                Some("ctrl") => mask.ctrl = true,
+                Some("alt") => mask.alt = true,
+                Some("gui") => mask.gui = true,
+                Some("altgr") => mask.altgr = true,
+                _ => {
+                    error!(
+                        "keyboard_modifier_mask setting expected shift, ctrl, alt, gui or altgr, but received: {}",
+                        name
+                    );
+                    return;
+                }
+            }
+        }
+
+        *self = mask;
+    }
+}
+
+impl From<ModifierMask> for Value {
+    fn from(mask: ModifierMask) -> Self {
+        let names = [
+            (mask.shift, "shift"),
+            (mask.ctrl, "ctrl"),
+            (mask.alt, "alt"),
+            (mask.gui, "gui"),
+            (mask.altgr, "altgr"),
+        ];
+
+        Value::Array(
+            names
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, name)| Value::from(*name))
+                .collect(),
+        )
+    }
+}
+
 #[derive(Clone)]
 struct KeyboardSettings {
     layout: KeyboardLayout,
+    physical_keys: bool,
+    modifier_mask: ModifierMask,
 }
 
 pub fn initialize_settings() {
     SETTINGS.set(&KeyboardSettings {
         layout: KeyboardLayout::Qwerty,
+        physical_keys: false,
+        modifier_mask: ModifierMask::default(),
     });
 
     register_nvim_setting!("keyboard_layout", KeyboardSettings::layout);
+    register_nvim_setting!("keyboard_physical_keys", KeyboardSettings::physical_keys);
+    register_nvim_setting!("keyboard_modifier_mask", KeyboardSettings::modifier_mask);
+}
+
+// Physical keys are bound by where they sit on the keyboard, named after the key in that spot on
+// a US layout. The text input is dropped since it comes from the active layout.
+#[cfg(feature = "sdl2")]
+pub fn resolve_physical_key(
+    keycode: Option<Keycode>,
+    scancode: Option<Scancode>,
+    keytext: Option<String>,
+) -> (Option<Keycode>, Option<String>) {
+    match scancode {
+        Some(scancode) if SETTINGS.get::<KeyboardSettings>().physical_keys => {
+            (Keycode::from_name(scancode.name()).or(keycode), None)
+        }
+        _ => (keycode, keytext),
+    }
+}
+
+// Modified keys don't produce text, so what the key types is remembered from when it was pressed
+// on its own. Only single characters are kept, since dead keys and input methods type more.
+pub fn learn_layout_text(
+    keycode: Option<Keycode>,
+    modifiers: KeyboardModifiers,
+    text: Option<&str>,
+) {
+    if let (Some(keycode), Some(text)) = (keycode, text) {
+        if modifiers.ctrl || modifiers.alt || modifiers.gui || modifiers.altgr {
+            return;
+        }
+        if text.chars().count() == 1 {
+            LAYOUT_TEXT
+                .lock()
+                .unwrap()
+                .insert((keycode, modifiers.shift), text.to_string());
+        }
+    }
+}
+
+// The same keycodes can type something else once the layout is switched
+pub fn forget_layout_text() {
+    LAYOUT_TEXT.lock().unwrap().clear();
+}
+
+// Letters keep the S- modifier rather than turning into capitals, the same as on the qwerty
+// layout, so only the other characters are taken from the active layout
+fn handle_system_layout(
+    keycode: Keycode,
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+) -> Option<(String, bool, bool, bool, bool)> {
+    let layout_text = LAYOUT_TEXT.lock().unwrap().get(&(keycode, shift)).cloned();
+    match layout_text {
+        Some(text) if !text.chars().all(char::is_alphabetic) => {
+            Some((text, false, false, ctrl, alt))
+        }
+        _ => handle_qwerty_layout(keycode, shift, ctrl, alt)
+            .map(|(text, special, shift, ctrl, alt)| (text.to_string(), special, shift, ctrl, alt)),
+    }
+}
+
+// Dead keys send a key down without any text, and the composed character only arrives as text
+// with the key after. The key down of a dead key is held back until then, so neither it nor the
+// composed character is sent twice. Keys whose text comes a frame late are held the same way.
+#[derive(Clone, Debug, Default)]
+pub struct DeadKeyState {
+    pending: Option<Keycode>,
+}
+
+impl DeadKeyState {
+    pub fn filter(
+        &mut self,
+        keycode: Option<Keycode>,
+        text: Option<String>,
+        modifiers: KeyboardModifiers,
+    ) -> (Option<Keycode>, Option<String>) {
+        let modified = modifiers.ctrl || modifiers.alt || modifiers.gui;
+        match (keycode, &text) {
+            (Some(keycode), None) if !modified && types_text(keycode) => {
+                if let Some(pending) = self.pending.replace(keycode) {
+                    trace!("Dead key {:?} produced no text", pending);
+                }
+                (None, None)
+            }
+            // Anything else, like escape or a chord, ends the composition
+            _ => {
+                self.pending = None;
+                (keycode, text)
+            }
+        }
+    }
+}
+
+// Keys that type a character when pressed on their own, rather than naming a special key
+fn types_text(keycode: Keycode) -> bool {
+    handle_qwerty_layout(keycode, false, false, false).map_or(false, |(_, special, ..)| !special)
 }
 
 fn append_modifiers(
@@ -99,57 +357,166 @@ fn append_modifiers(
     result
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KeyboardModifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+    pub altgr: bool,
+}
+
+impl KeyboardModifiers {
+    pub fn masked(self, mask: ModifierMask) -> KeyboardModifiers {
+        KeyboardModifiers {
+            shift: self.shift && mask.shift,
+            ctrl: self.ctrl && mask.ctrl,
+            alt: (self.alt && mask.alt) || (self.altgr && mask.altgr),
+            gui: self.gui && mask.gui,
+            altgr: self.altgr && mask.altgr,
+        }
+    }
+}
+
 #[cfg(feature = "sdl2")]
-pub fn produce_neovim_keybinding_string(
+impl From<Mod> for KeyboardModifiers {
+    fn from(modifiers: Mod) -> Self {
+        KeyboardModifiers {
+            shift: modifiers.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            ctrl: modifiers.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: modifiers.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            gui: modifiers.intersects(Mod::LGUIMOD | Mod::RGUIMOD),
+            altgr: modifiers.contains(Mod::MODEMOD),
+        }
+    }
+}
+
+#[cfg(feature = "winit")]
+impl From<Option<ModifiersState>> for KeyboardModifiers {
+    fn from(modifiers: Option<ModifiersState>) -> Self {
+        modifiers
+            .map(|modifiers| KeyboardModifiers {
+                shift: modifiers.shift(),
+                ctrl: modifiers.ctrl(),
+                alt: modifiers.alt(),
+                gui: modifiers.logo(),
+                altgr: false,
+            })
+            .unwrap_or_default()
+    }
+}
+
+// SDL doesn't report scroll lock in its modifier state, so only caps and num lock are tracked
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LockKeys {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+}
+
+#[cfg(feature = "sdl2")]
+impl From<Mod> for LockKeys {
+    fn from(modifiers: Mod) -> Self {
+        LockKeys {
+            caps_lock: modifiers.contains(Mod::CAPSMOD),
+            num_lock: modifiers.contains(Mod::NUMMOD),
+        }
+    }
+}
+
+pub fn try_produce_neovim_keybinding_string(
     keycode: Option<Keycode>,
     keytext: Option<String>,
-    modifiers: Mod,
-) -> Option<String> {
-    let shift = modifiers.contains(Mod::LSHIFTMOD) || modifiers.contains(Mod::RSHIFTMOD);
-    let ctrl = modifiers.contains(Mod::LCTRLMOD) || modifiers.contains(Mod::RCTRLMOD);
-    let alt = modifiers.contains(Mod::LALTMOD) || modifiers.contains(Mod::RALTMOD);
-    let gui = modifiers.contains(Mod::LGUIMOD) || modifiers.contains(Mod::RGUIMOD);
+    modifiers: KeyboardModifiers,
+) -> Result<String, KeybindingError> {
+    let KeyboardModifiers {
+        shift,
+        ctrl,
+        alt,
+        gui,
+        ..
+    } = modifiers.masked(SETTINGS.get::<KeyboardSettings>().modifier_mask);
+
     if let Some(text) = keytext {
-        Some(append_modifiers(&text, false, false, ctrl, alt, gui))
+        if text.is_empty() {
+            Err(KeybindingError::EmptyText)
+        } else {
+            Ok(append_modifiers(&text, false, false, ctrl, alt, gui))
+        }
     } else if let Some(keycode) = keycode {
+        if is_modifier_key(keycode) {
+            return Err(KeybindingError::ModifierOnly(keycode));
+        }
+
         (match SETTINGS.get::<KeyboardSettings>().layout {
-            KeyboardLayout::Qwerty => handle_qwerty_layout(keycode, shift, ctrl, alt),
+            KeyboardLayout::System => handle_system_layout(keycode, shift, ctrl, alt),
+            KeyboardLayout::Qwerty => handle_qwerty_layout(keycode, shift, ctrl, alt).map(
+                |(text, special, shift, ctrl, alt)| (text.to_string(), special, shift, ctrl, alt),
+            ),
         })
         .map(|(transformed_text, special, shift, ctrl, alt)| {
-            append_modifiers(transformed_text, special, shift, ctrl, alt, gui)
+            append_modifiers(&transformed_text, special, shift, ctrl, alt, gui)
         })
+        .ok_or(KeybindingError::UnsupportedKey(keycode))
     } else {
-        None
+        Err(KeybindingError::NoInput)
     }
 }
 
-#[cfg(feature = "winit")]
 pub fn produce_neovim_keybinding_string(
     keycode: Option<Keycode>,
     keytext: Option<String>,
-    modifiers: Option<ModifiersState>,
+    modifiers: KeyboardModifiers,
 ) -> Option<String> {
-    let mut shift = false;
-    let mut ctrl = false;
-    let mut alt = false;
-    let mut gui = false;
-    if let Some(modifiers) = modifiers {
-        shift = modifiers.shift();
-        ctrl = modifiers.ctrl();
-        alt = modifiers.alt();
-        gui = modifiers.logo();
+    match try_produce_neovim_keybinding_string(keycode, keytext, modifiers) {
+        Ok(keybinding) => Some(keybinding),
+        // Every frame checks for input, so an empty frame isn't worth reporting
+        Err(KeybindingError::NoInput) => None,
+        Err(error) => {
+            debug!("Keybinding dropped: {}", error);
+            None
+        }
     }
+}
 
-    if let Some(text) = keytext {
-        Some(append_modifiers(&text, false, false, ctrl, alt, gui))
-    } else if let Some(keycode) = keycode {
-        (match SETTINGS.get::<KeyboardSettings>().layout {
-            KeyboardLayout::Qwerty => handle_qwerty_layout(keycode, shift, ctrl, alt),
-        })
-        .map(|(transformed_text, special, shift, ctrl, alt)| {
-            append_modifiers(transformed_text, special, shift, ctrl, alt, gui)
-        })
-    } else {
-        None
+#[cfg(all(test, feature = "sdl2"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_layout_uses_typed_text() {
+        // The key left of 2 on an azerty layout types & on its own and 1 with shift
+        let shift = KeyboardModifiers {
+            shift: true,
+            ..Default::default()
+        };
+        learn_layout_text(Some(Keycode::Ampersand), shift, Some("1"));
+
+        assert_eq!(
+            handle_system_layout(Keycode::Ampersand, true, true, false),
+            Some((String::from("1"), false, false, true, false))
+        );
+        assert_eq!(
+            handle_system_layout(Keycode::A, true, true, false),
+            Some((String::from("a"), false, true, true, false))
+        );
+    }
+
+    #[test]
+    fn test_dead_key_sends_only_composed_text() {
+        let mut dead_keys = DeadKeyState::default();
+        let modifiers = KeyboardModifiers::default();
+
+        assert_eq!(
+            dead_keys.filter(Some(Keycode::Quote), None, modifiers),
+            (None, None)
+        );
+        assert_eq!(
+            dead_keys.filter(Some(Keycode::E), Some(String::from("é")), modifiers),
+            (Some(Keycode::E), Some(String::from("é")))
+        );
+        assert_eq!(
+            dead_keys.filter(Some(Keycode::Escape), None, modifiers),
+            (Some(Keycode::Escape), None)
+        );
     }
 }
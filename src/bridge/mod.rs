@@ -17,6 +17,7 @@ use tokio::runtime::Runtime;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 use crate::error_handling::ResultPanicExplanation;
+use crate::redraw_scheduler::REDRAW_SCHEDULER;
 use crate::settings::*;
 use crate::window::window_geometry_or_default;
 pub use events::*;
@@ -80,7 +81,6 @@ fn build_nvim_cmd() -> Command {
     }
 }
 
-#[cfg(windows)]
 pub fn build_neovide_command(channel: u64, num_args: u64, command: &str, event: &str) -> String {
     let nargs: String = if num_args > 1 {
         "+".to_string()
@@ -144,6 +144,7 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
             Ok(Ok(())) => {}
         };
         BRIDGE.running.store(false, Ordering::Relaxed);
+        REDRAW_SCHEDULER.wake();
     });
 
     if let Ok(Value::Integer(correct_version)) = nvim.eval("has(\"nvim-0.4\")").await {
@@ -226,6 +227,15 @@ async fn start_process(mut receiver: UnboundedReceiver<UiCommand>) {
     .await
     .ok();
 
+    nvim.command(&build_neovide_command(
+        neovide_channel,
+        1,
+        "NeovideScreenshot",
+        "capture_frame",
+    ))
+    .await
+    .ok();
+
     nvim.ui_attach(width as i64, height as i64, &options)
         .await
         .unwrap_or_explained_panic("Could not attach ui to neovim process");
@@ -1,6 +1,7 @@
 use log::trace;
 use nvim_rs::compat::tokio::Compat;
 use nvim_rs::Neovim;
+use rmpv::Value;
 use tokio::process::ChildStdin;
 
 use crate::editor::EDITOR;
@@ -17,6 +18,7 @@ pub enum UiCommand {
     },
     Keyboard(String),
     MouseButton {
+        button: String,
         action: String,
         position: (u32, u32),
     },
@@ -24,10 +26,43 @@ pub enum UiCommand {
         direction: String,
         position: (u32, u32),
     },
-    Drag(u32, u32),
+    Drag {
+        button: String,
+        position: (u32, u32),
+    },
     FileDrop(String),
+    ImePreedit {
+        text: String,
+        cursor: (i32, i32),
+    },
+    Touch {
+        phase: String,
+        finger: i64,
+        position: (u32, u32),
+        pressure: f32,
+    },
+    RawKey {
+        pressed: bool,
+        keycode: String,
+        scancode: String,
+        modifiers: Vec<String>,
+        repeat: bool,
+    },
+    Controller {
+        controller: u32,
+        event: String,
+        input: Option<String>,
+        value: f32,
+    },
+    Clipboard(String),
+    Displays(Value),
+    FrameStats(Value),
+    FrameCaptured(Value),
+    MenuItem(String),
     FocusLost,
     FocusGained,
+    UserEvent(String),
+    CloseRequested,
     Quit,
     #[cfg(windows)]
     RegisterRightClick,
@@ -47,11 +82,12 @@ impl UiCommand {
                 nvim.input(&input_command).await.expect("Input failed");
             }
             UiCommand::MouseButton {
+                button,
                 action,
                 position: (grid_x, grid_y),
             } => {
                 if EDITOR.lock().mouse_enabled {
-                    nvim.input_mouse("left", &action, "", 0, grid_y as i64, grid_x as i64)
+                    nvim.input_mouse(&button, &action, "", 0, grid_y as i64, grid_x as i64)
                         .await
                         .expect("Mouse Input Failed");
                 }
@@ -66,9 +102,12 @@ impl UiCommand {
                         .expect("Mouse Scroll Failed");
                 }
             }
-            UiCommand::Drag(grid_x, grid_y) => {
+            UiCommand::Drag {
+                button,
+                position: (grid_x, grid_y),
+            } => {
                 if EDITOR.lock().mouse_enabled {
-                    nvim.input_mouse("left", "drag", "", 0, grid_y as i64, grid_x as i64)
+                    nvim.input_mouse(&button, "drag", "", 0, grid_y as i64, grid_x as i64)
                         .await
                         .expect("Mouse Drag Failed");
                 }
@@ -81,6 +120,112 @@ impl UiCommand {
                 .command("if exists('#FocusGained') | doautocmd <nomodeline> FocusGained | endif")
                 .await
                 .expect("Focus Gained Failed"),
+            // Neovim has no notion of text being composed, so it is exposed for configs to show
+            // however they like. The cursor is the start and length of the selected part.
+            UiCommand::ImePreedit {
+                text,
+                cursor: (start, length),
+            } => {
+                nvim.set_var("neovide_ime_preedit", Value::from(text))
+                    .await
+                    .ok();
+                nvim.set_var(
+                    "neovide_ime_preedit_cursor",
+                    Value::Array(vec![Value::from(start), Value::from(length)]),
+                )
+                .await
+                .ok();
+                execute_user_event(nvim, "NeovideImePreedit").await;
+            }
+            UiCommand::RawKey {
+                pressed,
+                keycode,
+                scancode,
+                modifiers,
+                repeat,
+            } => {
+                let key = Value::Map(vec![
+                    (Value::from("keycode"), Value::from(keycode)),
+                    (Value::from("scancode"), Value::from(scancode)),
+                    (
+                        Value::from("modifiers"),
+                        Value::Array(modifiers.into_iter().map(Value::from).collect()),
+                    ),
+                    (Value::from("repeat"), Value::from(repeat)),
+                ]);
+                nvim.set_var("neovide_raw_key", key).await.ok();
+
+                let event_name = if pressed {
+                    "NeovideKeyDown"
+                } else {
+                    "NeovideKeyUp"
+                };
+                execute_user_event(nvim, event_name).await;
+            }
+            UiCommand::Touch {
+                phase,
+                finger,
+                position: (grid_x, grid_y),
+                pressure,
+            } => {
+                let touch = Value::Map(vec![
+                    (Value::from("finger"), Value::from(finger)),
+                    (Value::from("column"), Value::from(grid_x)),
+                    (Value::from("row"), Value::from(grid_y)),
+                    (Value::from("pressure"), Value::from(pressure)),
+                ]);
+                nvim.set_var("neovide_touch", touch).await.ok();
+                execute_user_event(nvim, &format!("NeovideTouch{}", phase)).await;
+            }
+            UiCommand::Controller {
+                controller,
+                event,
+                input,
+                value,
+            } => {
+                let controller_event = Value::Map(vec![
+                    (Value::from("controller"), Value::from(controller)),
+                    (
+                        Value::from("input"),
+                        input.map(Value::from).unwrap_or(Value::Nil),
+                    ),
+                    (Value::from("value"), Value::from(value)),
+                ]);
+                nvim.set_var("neovide_controller", controller_event)
+                    .await
+                    .ok();
+                execute_user_event(nvim, &format!("NeovideController{}", event)).await;
+            }
+            UiCommand::Clipboard(text) => {
+                nvim.set_var("neovide_clipboard", Value::from(text))
+                    .await
+                    .ok();
+                execute_user_event(nvim, "NeovideClipboard").await;
+            }
+            UiCommand::Displays(displays) => {
+                nvim.set_var("neovide_displays", displays).await.ok();
+                execute_user_event(nvim, "NeovideDisplays").await;
+            }
+            UiCommand::FrameStats(frame_stats) => {
+                nvim.set_var("neovide_frame_stats", frame_stats).await.ok();
+                execute_user_event(nvim, "NeovideFrameStats").await;
+            }
+            UiCommand::FrameCaptured(frame) => {
+                nvim.set_var("neovide_captured_frame", frame).await.ok();
+                execute_user_event(nvim, "NeovideFrameCaptured").await;
+            }
+            UiCommand::MenuItem(id) => {
+                nvim.set_var("neovide_menu_item", Value::from(id))
+                    .await
+                    .ok();
+                execute_user_event(nvim, "NeovideMenuItem").await;
+            }
+            UiCommand::UserEvent(event_name) => execute_user_event(nvim, &event_name).await,
+            UiCommand::CloseRequested => {
+                // Neovim prompts about any unsaved buffers and stays open if the user cancels.
+                // The window closes on its own once the process exits.
+                nvim.command("confirm qa").await.ok();
+            }
             UiCommand::Quit => {
                 nvim.command("qa!").await.ok(); // Ignoring result as it won't succeed since the app closed.
             }
@@ -120,3 +265,11 @@ impl UiCommand {
         matches!(self, UiCommand::Resize { .. })
     }
 }
+
+async fn execute_user_event(nvim: &Neovim<Compat<ChildStdin>>, event_name: &str) {
+    let command = format!(
+        "if exists('#User#{0}') | doautocmd <nomodeline> User {0} | endif",
+        event_name
+    );
+    nvim.command(&command).await.ok();
+}
@@ -29,8 +29,19 @@ pub struct DrawCommand {
     pub style: Option<Arc<Style>>,
 }
 
+// A region of the grid that neovim scrolled, kept for the renderer to animate
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionScroll {
+    pub top: u64,
+    pub bottom: u64,
+    pub left: u64,
+    pub right: u64,
+    pub rows: i64,
+}
+
 pub struct Editor {
     pub grid: CharacterGrid,
+    pub scrolls: Vec<RegionScroll>,
     pub title: String,
     pub mouse_enabled: bool,
     pub guifont: Option<String>,
@@ -46,6 +57,7 @@ impl Editor {
     pub fn new() -> Editor {
         Editor {
             grid: CharacterGrid::new(window_geometry_or_default()),
+            scrolls: Vec::new(),
             title: "Neovide".to_string(),
             mouse_enabled: true,
             guifont: None,
@@ -296,6 +308,15 @@ impl Editor {
                 }
             }
         }
+        if rows != 0 {
+            self.scrolls.push(RegionScroll {
+                top,
+                bottom: bot,
+                left,
+                right,
+                rows,
+            });
+        }
         trace!("Region scrolled");
     }
 
@@ -2,6 +2,8 @@
 
 #[macro_use]
 mod settings;
+#[macro_use]
+mod profiling;
 
 mod bridge;
 mod editor;
@@ -61,9 +63,13 @@ fn main() {
 
     window::initialize_settings();
     redraw_scheduler::initialize_settings();
+    renderer::initialize_settings();
     renderer::cursor_renderer::initialize_settings();
     bridge::layouts::initialize_settings();
 
     initialize(&BRIDGE);
-    ui_loop();
+    if let Err(err) = ui_loop() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
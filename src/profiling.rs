@@ -0,0 +1,31 @@
+// Lasts until dropped. With the tracing feature enabled it holds a tracing span, so a subscriber
+// such as tracing-tracy or puffin's sees the time spent handling events, drawing and presenting.
+// Without the feature it is empty and costs nothing.
+#[cfg_attr(not(feature = "tracing"), derive(Default))]
+pub struct ProfileSpan {
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+#[cfg(feature = "tracing")]
+impl ProfileSpan {
+    pub fn new(span: tracing::span::EnteredSpan) -> ProfileSpan {
+        ProfileSpan { _span: span }
+    }
+}
+
+// tracing needs the span name at compile time, so spans are made through this rather than
+// ProfileSpan directly
+#[cfg(not(feature = "tracing"))]
+macro_rules! profile_span {
+    ($name:literal) => {
+        $crate::profiling::ProfileSpan::default()
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! profile_span {
+    ($name:literal) => {
+        $crate::profiling::ProfileSpan::new(tracing::trace_span!($name).entered())
+    };
+}
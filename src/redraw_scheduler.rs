@@ -1,8 +1,9 @@
-use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use std::sync::Mutex;
 use std::time::Instant;
 
 use log::trace;
+use skulpin::skia_safe::Rect;
 
 use crate::settings::*;
 
@@ -32,9 +33,47 @@ pub fn initialize_settings() {
     register_nvim_setting!("extra_buffer_frames", RedrawSettings::extra_buffer_frames);
 }
 
+// The parts of the window that changed since the last frame, in logical pixels
+#[derive(Clone, Debug, PartialEq)]
+pub enum Damage {
+    Full,
+    Regions(Vec<Rect>),
+}
+
+impl Damage {
+    fn add(&mut self, rect: Rect) {
+        if let Damage::Regions(regions) = self {
+            // Overlapping rectangles are merged so the list stays short
+            match regions.iter_mut().find(|region| region.intersects(rect)) {
+                Some(region) => region.join(rect),
+                None => regions.push(rect),
+            }
+        }
+    }
+
+    // The smallest rectangle covering all of the damage, or None when the whole window changed
+    pub fn bounds(&self) -> Option<Rect> {
+        match self {
+            Damage::Full => None,
+            Damage::Regions(regions) => {
+                let mut regions = regions.iter();
+                let mut bounds = *regions.next()?;
+                for region in regions {
+                    bounds.join(region);
+                }
+                Some(bounds)
+            }
+        }
+    }
+}
+
 pub struct RedrawScheduler {
     frames_queued: AtomicU16,
     scheduled_frame: Mutex<Option<Instant>>,
+    idle: AtomicBool,
+    waker: Mutex<Option<Box<dyn Fn() + Send>>>,
+    animations: Mutex<Vec<Box<dyn FnMut(f32) -> bool + Send>>>,
+    damage: Mutex<Damage>,
 }
 
 impl RedrawScheduler {
@@ -42,6 +81,72 @@ impl RedrawScheduler {
         RedrawScheduler {
             frames_queued: AtomicU16::new(1),
             scheduled_frame: Mutex::new(None),
+            idle: AtomicBool::new(false),
+            waker: Mutex::new(None),
+            animations: Mutex::new(Vec::new()),
+            damage: Mutex::new(Damage::Full),
+        }
+    }
+
+    // Called from any thread to interrupt the window while it is idle, such as by pushing an event
+    // it is waiting on
+    pub fn set_waker(&self, waker: Option<Box<dyn Fn() + Send>>) {
+        *self.waker.lock().unwrap() = waker;
+    }
+
+    // The window marks itself idle before it blocks, so only the first change after that has to
+    // wake it
+    pub fn start_idle(&self) {
+        self.idle.store(true, Ordering::SeqCst);
+    }
+
+    pub fn stop_idle(&self) {
+        self.idle.store(false, Ordering::SeqCst);
+    }
+
+    pub fn wake(&self) {
+        if self.idle.swap(false, Ordering::SeqCst) {
+            trace!("Waking idle window");
+            if let Some(waker) = &*self.waker.lock().unwrap() {
+                waker();
+            }
+        }
+    }
+
+    // Runs the animation before every drawn frame with the seconds since the last one, and keeps
+    // frames coming until it returns false
+    pub fn animate(&self, animation: impl FnMut(f32) -> bool + Send + 'static) {
+        self.animations.lock().unwrap().push(Box::new(animation));
+        self.queue_next_frame();
+    }
+
+    pub fn run_animations(&self, dt: f32) {
+        // Taken out of the lock so an animation can start another one
+        let animations = std::mem::take(&mut *self.animations.lock().unwrap());
+        if animations.is_empty() {
+            return;
+        }
+
+        let mut running: Vec<_> = animations
+            .into_iter()
+            .filter_map(|mut animation| if animation(dt) { Some(animation) } else { None })
+            .collect();
+
+        let mut animations = self.animations.lock().unwrap();
+        running.append(&mut animations);
+        *animations = running;
+        if !animations.is_empty() {
+            drop(animations);
+            self.queue_next_frame();
+        }
+    }
+
+    // When the next frame is due, or None if nothing has asked for one
+    pub fn next_frame(&self) -> Option<Instant> {
+        if self.frames_queued.load(Ordering::Relaxed) > 0 {
+            Some(Instant::now())
+        } else {
+            *self.scheduled_frame.lock().unwrap()
         }
     }
 
@@ -56,14 +161,44 @@ impl RedrawScheduler {
         } else {
             *scheduled_frame = Some(new_scheduled);
         }
+        drop(scheduled_frame);
+
+        self.wake();
     }
 
     pub fn queue_next_frame(&self) {
+        *self.damage.lock().unwrap() = Damage::Full;
+        self.queue_frames();
+    }
+
+    // Asks for a frame that only has to update this part of the window
+    pub fn queue_redraw_rect(&self, rect: Rect) {
+        trace!("Redraw queued for {:?}", rect);
+        self.damage.lock().unwrap().add(rect);
+        self.queue_frames();
+    }
+
+    // Taken when a frame is drawn. Frames that nothing described the changes for, such as
+    // scheduled ones, redraw everything.
+    pub fn take_damage(&self) -> Damage {
+        let damage = std::mem::replace(
+            &mut *self.damage.lock().unwrap(),
+            Damage::Regions(Vec::new()),
+        );
+        match damage {
+            Damage::Regions(regions) if regions.is_empty() => Damage::Full,
+            damage => damage,
+        }
+    }
+
+    fn queue_frames(&self) {
         trace!("Next frame queued");
         let buffer_frames = SETTINGS.get::<RedrawSettings>().extra_buffer_frames;
 
         self.frames_queued
             .store(buffer_frames as u16, Ordering::Relaxed);
+
+        self.wake();
     }
 
     pub fn should_draw(&self) -> bool {
@@ -89,3 +224,26 @@ impl RedrawScheduler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_merges_overlapping_regions() {
+        let mut damage = Damage::Regions(Vec::new());
+        damage.add(Rect::from_xywh(0.0, 0.0, 10.0, 10.0));
+        damage.add(Rect::from_xywh(5.0, 5.0, 10.0, 10.0));
+        damage.add(Rect::from_xywh(50.0, 50.0, 10.0, 10.0));
+
+        assert_eq!(
+            damage,
+            Damage::Regions(vec![
+                Rect::from_xywh(0.0, 0.0, 15.0, 15.0),
+                Rect::from_xywh(50.0, 50.0, 10.0, 10.0)
+            ])
+        );
+        assert_eq!(damage.bounds(), Some(Rect::from_xywh(0.0, 0.0, 60.0, 60.0)));
+        assert_eq!(Damage::Full.bounds(), None);
+    }
+}
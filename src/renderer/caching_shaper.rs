@@ -251,6 +251,7 @@ struct FontSet {
     normal: FontCollection,
     bold: FontCollection,
     italic: FontCollection,
+    bold_italic: FontCollection,
 }
 
 impl FontSet {
@@ -262,14 +263,17 @@ impl FontSet {
                 .build_collection_by_font_name(fallback_list, build_properties(true, false)),
             italic: loader
                 .build_collection_by_font_name(fallback_list, build_properties(false, true)),
+            bold_italic: loader
+                .build_collection_by_font_name(fallback_list, build_properties(true, true)),
         }
     }
 
     fn get(&self, bold: bool, italic: bool) -> &FontCollection {
         match (bold, italic) {
-            (true, _) => &self.bold,
             (false, false) => &self.normal,
+            (true, false) => &self.bold,
             (false, true) => &self.italic,
+            (true, true) => &self.bold_italic,
         }
     }
 }
@@ -369,6 +373,10 @@ impl CachingShaper {
         self.blob_cache.get(&key).unwrap()
     }
 
+    pub fn clear_shape_cache(&mut self) {
+        self.blob_cache.clear();
+    }
+
     pub fn update_font(&mut self, guifont_setting: &str) -> bool {
         let updated = self.options.update(guifont_setting);
         if updated {
@@ -1,5 +1,8 @@
 use std::time::{Duration, Instant};
 
+use log::trace;
+
+use crate::bridge::{UiCommand, BRIDGE};
 use crate::editor::Cursor;
 use crate::redraw_scheduler::REDRAW_SCHEDULER;
 
@@ -24,10 +27,34 @@ impl BlinkStatus {
         }
     }
 
-    pub fn update_status(&mut self, new_cursor: &Cursor) -> bool {
+    // With a blink interval set, neovim is told about every change with the NeovideBlinkOn and
+    // NeovideBlinkOff user events so configs can follow the blink
+    pub fn update_status(&mut self, new_cursor: &Cursor, blink_interval: u64) -> bool {
+        let (visible, toggled) = self.update_status_at(new_cursor, blink_interval, Instant::now());
+        if let Some(on) = toggled {
+            trace!("Cursor blink toggled: {}", on);
+            if blink_interval > 0 {
+                let event_name = if on {
+                    "NeovideBlinkOn"
+                } else {
+                    "NeovideBlinkOff"
+                };
+                BRIDGE.queue_command(UiCommand::UserEvent(String::from(event_name)));
+            }
+        }
+        visible
+    }
+
+    // Returns whether the cursor is visible, and its new state if it just blinked
+    fn update_status_at(
+        &mut self,
+        new_cursor: &Cursor,
+        blink_interval: u64,
+        now: Instant,
+    ) -> (bool, Option<bool>) {
         if self.previous_cursor.is_none() || new_cursor != self.previous_cursor.as_ref().unwrap() {
             self.previous_cursor = Some(new_cursor.clone());
-            self.last_transition = Instant::now();
+            self.last_transition = now;
             if new_cursor.blinkwait.is_some() && new_cursor.blinkwait != Some(0) {
                 self.state = BlinkState::Waiting;
             } else {
@@ -35,23 +62,28 @@ impl BlinkStatus {
             }
         }
 
-        if new_cursor.blinkwait == Some(0)
-            || new_cursor.blinkoff == Some(0)
-            || new_cursor.blinkon == Some(0)
-        {
-            return true;
+        // A non zero blink interval overrides the on and off durations requested by guicursor
+        let (blinkon, blinkoff) = if blink_interval > 0 {
+            (Some(blink_interval), Some(blink_interval))
+        } else {
+            (new_cursor.blinkon, new_cursor.blinkoff)
+        };
+
+        if new_cursor.blinkwait == Some(0) || blinkoff == Some(0) || blinkon == Some(0) {
+            return (true, None);
         }
 
         let delay = match self.state {
             BlinkState::Waiting => new_cursor.blinkwait,
-            BlinkState::Off => new_cursor.blinkoff,
-            BlinkState::On => new_cursor.blinkon,
+            BlinkState::Off => blinkoff,
+            BlinkState::On => blinkon,
         }
         .filter(|millis| *millis > 0)
         .map(Duration::from_millis);
 
+        let mut toggled = None;
         if delay
-            .map(|delay| self.last_transition + delay < Instant::now())
+            .map(|delay| self.last_transition + delay < now)
             .unwrap_or(false)
         {
             self.state = match self.state {
@@ -59,13 +91,14 @@ impl BlinkStatus {
                 BlinkState::On => BlinkState::Off,
                 BlinkState::Off => BlinkState::On,
             };
-            self.last_transition = Instant::now();
+            self.last_transition = now;
+            toggled = Some(matches!(self.state, BlinkState::On));
         }
 
         let scheduled_frame = (match self.state {
             BlinkState::Waiting => new_cursor.blinkwait,
-            BlinkState::Off => new_cursor.blinkoff,
-            BlinkState::On => new_cursor.blinkon,
+            BlinkState::Off => blinkoff,
+            BlinkState::On => blinkon,
         })
         .map(|delay| self.last_transition + Duration::from_millis(delay));
 
@@ -73,9 +106,44 @@ impl BlinkStatus {
             REDRAW_SCHEDULER.schedule(scheduled_frame);
         }
 
-        match self.state {
+        let visible = match self.state {
             BlinkState::Waiting | BlinkState::Off => false,
             BlinkState::On => true,
-        }
+        };
+        (visible, toggled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blink_interval_toggles_once_per_interval() {
+        let cursor = Cursor::new();
+        let mut blink_status = BlinkStatus::new();
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        assert_eq!(
+            blink_status.update_status_at(&cursor, 500, at(0)),
+            (true, None)
+        );
+        assert_eq!(
+            blink_status.update_status_at(&cursor, 500, at(300)),
+            (true, None)
+        );
+        assert_eq!(
+            blink_status.update_status_at(&cursor, 500, at(501)),
+            (false, Some(false))
+        );
+        assert_eq!(
+            blink_status.update_status_at(&cursor, 500, at(800)),
+            (false, None)
+        );
+        assert_eq!(
+            blink_status.update_status_at(&cursor, 500, at(1002)),
+            (true, Some(true))
+        );
     }
 }
@@ -1,7 +1,8 @@
-mod animation_utils;
+pub mod animation_utils;
 mod blink;
 mod cursor_vfx;
 
+use log::error;
 use skulpin::skia_safe::{Canvas, Paint, Path, Point};
 
 use crate::editor::{Colors, Cursor, CursorShape, EDITOR};
@@ -20,10 +21,76 @@ const STANDARD_CORNERS: &[(f32, f32); 4] = &[(-0.5, -0.5), (0.5, -0.5), (0.5, 0.
 
 // ----------------------------------------------------------------------------
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum CursorEasing {
+    Linear,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InExpo,
+    OutExpo,
+}
+
+impl CursorEasing {
+    fn function(&self) -> fn(f32) -> f32 {
+        match self {
+            CursorEasing::Linear => ease_linear,
+            CursorEasing::InQuad => ease_in_quad,
+            CursorEasing::OutQuad => ease_out_quad,
+            CursorEasing::InOutQuad => ease_in_out_quad,
+            CursorEasing::InCubic => ease_in_cubic,
+            CursorEasing::OutCubic => ease_out_cubic,
+            CursorEasing::InOutCubic => ease_in_out_cubic,
+            CursorEasing::InExpo => ease_in_expo,
+            CursorEasing::OutExpo => ease_out_expo,
+        }
+    }
+}
+
+impl FromValue for CursorEasing {
+    fn from_value(&mut self, value: Value) {
+        *self = match value.as_str() {
+            Some("linear") => CursorEasing::Linear,
+            Some("in_quad") => CursorEasing::InQuad,
+            Some("out_quad") => CursorEasing::OutQuad,
+            Some("in_out_quad") => CursorEasing::InOutQuad,
+            Some("in_cubic") => CursorEasing::InCubic,
+            Some("out_cubic") => CursorEasing::OutCubic,
+            Some("in_out_cubic") => CursorEasing::InOutCubic,
+            Some("in_expo") => CursorEasing::InExpo,
+            Some("out_expo") => CursorEasing::OutExpo,
+            _ => {
+                error!("Expected a cursor easing name, but received {:?}", value);
+                return;
+            }
+        };
+    }
+}
+
+impl From<CursorEasing> for Value {
+    fn from(easing: CursorEasing) -> Self {
+        Value::from(match easing {
+            CursorEasing::Linear => "linear",
+            CursorEasing::InQuad => "in_quad",
+            CursorEasing::OutQuad => "out_quad",
+            CursorEasing::InOutQuad => "in_out_quad",
+            CursorEasing::InCubic => "in_cubic",
+            CursorEasing::OutCubic => "out_cubic",
+            CursorEasing::InOutCubic => "in_out_cubic",
+            CursorEasing::InExpo => "in_expo",
+            CursorEasing::OutExpo => "out_expo",
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct CursorSettings {
     antialiasing: bool,
     animation_length: f32,
+    animation_easing: CursorEasing,
     animate_in_insert_mode: bool,
     trail_size: f32,
     vfx_mode: cursor_vfx::VfxMode,
@@ -33,12 +100,14 @@ pub struct CursorSettings {
     vfx_particle_speed: f32,
     vfx_particle_phase: f32,
     vfx_particle_curl: f32,
+    blink_interval: u64,
 }
 
 pub fn initialize_settings() {
     SETTINGS.set(&CursorSettings {
         antialiasing: true,
         animation_length: 0.13,
+        animation_easing: CursorEasing::OutExpo,
         animate_in_insert_mode: true,
         trail_size: 0.7,
         vfx_mode: cursor_vfx::VfxMode::Disabled,
@@ -48,6 +117,7 @@ pub fn initialize_settings() {
         vfx_particle_speed: 10.0,
         vfx_particle_phase: 1.5,
         vfx_particle_curl: 1.0,
+        blink_interval: 0,
     });
 
     register_nvim_setting!("cursor_antialiasing", CursorSettings::antialiasing);
@@ -56,6 +126,7 @@ pub fn initialize_settings() {
         CursorSettings::animate_in_insert_mode
     );
     register_nvim_setting!("cursor_animation_length", CursorSettings::animation_length);
+    register_nvim_setting!("cursor_animation_easing", CursorSettings::animation_easing);
     register_nvim_setting!("cursor_trail_size", CursorSettings::trail_size);
     register_nvim_setting!("cursor_vfx_mode", CursorSettings::vfx_mode);
     register_nvim_setting!("cursor_vfx_opacity", CursorSettings::vfx_opacity);
@@ -79,6 +150,7 @@ pub fn initialize_settings() {
         "cursor_vfx_particle_curl",
         CursorSettings::vfx_particle_curl
     );
+    register_nvim_setting!("cursor_blink_interval", CursorSettings::blink_interval);
 }
 
 // ----------------------------------------------------------------------------
@@ -168,7 +240,7 @@ impl Corner {
         }
 
         self.current_position = ease_point(
-            ease_out_expo,
+            settings.animation_easing.function(),
             self.start_position,
             corner_destination,
             self.t,
@@ -244,8 +316,10 @@ impl CursorRenderer {
         dt: f32,
     ) {
         let (font_width, font_height) = font_size;
-        let render = self.blink_status.update_status(&cursor);
         let settings = SETTINGS.get::<CursorSettings>();
+        let render = self
+            .blink_status
+            .update_status(&cursor, settings.blink_interval);
 
         if settings.vfx_mode != self.previous_vfx_mode {
             self.cursor_vfx = cursor_vfx::new_cursor_vfx(&settings.vfx_mode);
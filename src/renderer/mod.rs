@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
 use log::trace;
+use skulpin::skia_safe::canvas::SrcRectConstraint;
 use skulpin::skia_safe::gpu::SurfaceOrigin;
-use skulpin::skia_safe::{colors, dash_path_effect, Budgeted, Canvas, Paint, Rect, Surface};
-use skulpin::CoordinateSystemHelper;
+use skulpin::skia_safe::{colors, dash_path_effect, Budgeted, Canvas, Image, Paint, Rect, Surface};
+use skulpin::LogicalSize;
 
 mod caching_shaper;
 pub mod cursor_renderer;
@@ -12,9 +13,39 @@ pub mod font_options;
 pub use caching_shaper::CachingShaper;
 pub use font_options::*;
 
-use crate::editor::{Style, EDITOR};
+use crate::editor::{RegionScroll, Style, EDITOR};
+use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::settings::*;
+use cursor_renderer::animation_utils::ease_out_cubic;
 use cursor_renderer::CursorRenderer;
 
+#[derive(Clone)]
+struct RendererSettings {
+    // In seconds. Scrolling jumps straight to the new position at 0.
+    scroll_animation_length: f32,
+}
+
+pub fn initialize_settings() {
+    SETTINGS.set(&RendererSettings {
+        scroll_animation_length: 0.0,
+    });
+
+    register_nvim_setting!(
+        "scroll_animation_length",
+        RendererSettings::scroll_animation_length
+    );
+}
+
+// The scrolled region slides from where its contents were to where they are now. Rows leaving the
+// region are drawn from a snapshot taken before the scroll, since the grid no longer has them.
+struct ScrollAnimation {
+    region: Rect,
+    previous: Image,
+    // Positive when the contents move up
+    distance: f32,
+    t: f32,
+}
+
 pub struct Renderer {
     surface: Option<Surface>,
     paint: Paint,
@@ -22,6 +53,8 @@ pub struct Renderer {
 
     pub font_width: f32,
     pub font_height: f32,
+    render_scale: f32,
+    scroll_animation: Option<ScrollAnimation>,
     cursor_renderer: CursorRenderer,
 }
 
@@ -42,10 +75,30 @@ impl Renderer {
             shaper,
             font_width,
             font_height,
+            render_scale: 1.0,
+            scroll_animation: None,
             cursor_renderer,
         }
     }
 
+    // The grid is rendered into an offscreen surface scaled by render_scale and then stretched
+    // over the whole window, so lowering it trades sharpness for fill rate. Window and mouse
+    // coordinates are unaffected.
+    pub fn set_render_scale(&mut self, render_scale: f32) {
+        let render_scale = render_scale.max(0.1);
+        if (self.render_scale - render_scale).abs() > std::f32::EPSILON {
+            self.render_scale = render_scale;
+            self.invalidate_surface();
+        }
+    }
+
+    // Drops the offscreen surface so it is rebuilt at the current resolution and fully redrawn
+    pub fn invalidate_surface(&mut self) {
+        self.surface = None;
+        EDITOR.lock().grid.set_dirty_all(true);
+        REDRAW_SCHEDULER.queue_next_frame();
+    }
+
     fn update_font(&mut self, guifont_setting: &str) -> bool {
         let updated = self.shaper.update_font(guifont_setting);
         if updated {
@@ -56,6 +109,10 @@ impl Renderer {
         updated
     }
 
+    pub fn release_caches(&mut self) {
+        self.shaper.clear_shape_cache();
+    }
+
     fn compute_text_region(&self, grid_pos: (u64, u64), cell_width: u64) -> Rect {
         let (grid_x, grid_y) = grid_pos;
         let x = grid_x as f32 * self.font_width;
@@ -149,18 +206,92 @@ impl Renderer {
         canvas.restore();
     }
 
-    pub fn draw(
+    fn start_scroll_animation(&mut self, scrolls: &[RegionScroll], previous: Image) {
+        let scroll = match scrolls.last() {
+            Some(scroll) => scroll,
+            None => return,
+        };
+
+        // Several scrolls of the same region in one frame are animated as one
+        let rows: i64 = scrolls
+            .iter()
+            .filter(|other| {
+                (other.top, other.bottom, other.left, other.right)
+                    == (scroll.top, scroll.bottom, scroll.left, scroll.right)
+            })
+            .map(|other| other.rows)
+            .sum();
+
+        self.scroll_animation = Some(ScrollAnimation {
+            region: Rect::new(
+                scroll.left as f32 * self.font_width,
+                scroll.top as f32 * self.font_height,
+                scroll.right as f32 * self.font_width,
+                scroll.bottom as f32 * self.font_height,
+            ),
+            previous,
+            distance: rows as f32 * self.font_height,
+            t: 0.0,
+        });
+    }
+
+    // image_scale is the number of image pixels per logical pixel
+    fn draw_scroll_animation(
         &mut self,
         gpu_canvas: &mut Canvas,
-        coordinate_system_helper: &CoordinateSystemHelper,
+        image: &Image,
+        image_scale: f32,
         dt: f32,
-    ) -> bool {
+    ) {
+        let animation_length = SETTINGS.get::<RendererSettings>().scroll_animation_length;
+        let animation = match &mut self.scroll_animation {
+            Some(animation) => animation,
+            None => return,
+        };
+
+        animation.t = (animation.t + dt / animation_length).min(1.0);
+        let offset = animation.distance * (1.0 - ease_out_cubic(animation.t));
+
+        let region = animation.region;
+        let source = Rect::new(
+            region.left * image_scale,
+            region.top * image_scale,
+            region.right * image_scale,
+            region.bottom * image_scale,
+        );
+
+        gpu_canvas.save();
+        gpu_canvas.clip_rect(region, None, Some(false));
+        gpu_canvas.draw_image_rect(
+            &animation.previous,
+            Some((&source, SrcRectConstraint::Strict)),
+            region.with_offset((0.0, offset - animation.distance)),
+            &self.paint,
+        );
+        gpu_canvas.draw_image_rect(
+            image,
+            Some((&source, SrcRectConstraint::Strict)),
+            region.with_offset((0.0, offset)),
+            &self.paint,
+        );
+        gpu_canvas.restore();
+
+        if animation.t < 1.0 {
+            REDRAW_SCHEDULER.queue_next_frame();
+        } else {
+            self.scroll_animation = None;
+        }
+    }
+
+    // The canvas is expected to already be in logical coordinates, whichever backend it comes from
+    pub fn draw(&mut self, gpu_canvas: &mut Canvas, window_size: LogicalSize, dt: f32) -> bool {
         trace!("Rendering");
 
-        let ((draw_commands, should_clear), default_style, cursor, guifont_setting) = {
+        let ((draw_commands, should_clear), scrolls, default_style, cursor, guifont_setting) = {
             let mut editor = EDITOR.lock();
             (
                 editor.build_draw_commands(),
+                std::mem::take(&mut editor.scrolls),
                 editor.default_style.clone(),
                 editor.cursor.clone(),
                 editor.guifont.clone(),
@@ -175,28 +306,48 @@ impl Renderer {
             self.surface = None;
         }
 
+        let render_scale = self.render_scale;
+        let surface_is_new = self.surface.is_none();
         let mut surface = self.surface.take().unwrap_or_else(|| {
-            let mut context = gpu_canvas.gpu_context().unwrap();
-            let budgeted = Budgeted::YES;
             let image_info = gpu_canvas.image_info();
-            let surface_origin = SurfaceOrigin::TopLeft;
-            let mut surface = Surface::new_render_target(
-                &mut context,
-                budgeted,
-                &image_info,
-                None,
-                surface_origin,
-                None,
-                None,
-            )
+            let image_info = image_info.with_dimensions((
+                (image_info.width() as f32 * render_scale) as i32,
+                (image_info.height() as f32 * render_scale) as i32,
+            ));
+            let mut surface = match gpu_canvas.gpu_context() {
+                Some(mut context) => Surface::new_render_target(
+                    &mut context,
+                    Budgeted::YES,
+                    &image_info,
+                    None,
+                    SurfaceOrigin::TopLeft,
+                    None,
+                    None,
+                ),
+                // Software rendering has no gpu, so the offscreen surface is in memory as well
+                None => Surface::new_raster(&image_info, None, None),
+            }
             .expect("Could not create surface");
             let canvas = surface.canvas();
             canvas.clear(default_style.colors.background.clone().unwrap().to_color());
             surface
         });
 
+        // A new surface has nothing to scroll from
+        let animate_scroll = !surface_is_new
+            && !font_changed
+            && SETTINGS.get::<RendererSettings>().scroll_animation_length > 0.0;
+        if animate_scroll && !scrolls.is_empty() {
+            let previous = surface.image_snapshot();
+            self.start_scroll_animation(&scrolls, previous);
+        } else if !animate_scroll {
+            self.scroll_animation = None;
+        }
+
+        let scale_factor = gpu_canvas.image_info().width() as f32 / window_size.width as f32;
         let mut canvas = surface.canvas();
-        coordinate_system_helper.use_logical_coordinates(&mut canvas);
+        canvas.reset_matrix();
+        canvas.scale((scale_factor * render_scale, scale_factor * render_scale));
 
         for command in draw_commands.iter() {
             self.draw_background(
@@ -220,7 +371,6 @@ impl Renderer {
         }
 
         let image = surface.image_snapshot();
-        let window_size = coordinate_system_helper.window_logical_size();
         let image_destination = Rect::new(
             0.0,
             0.0,
@@ -228,7 +378,9 @@ impl Renderer {
             window_size.height as f32,
         );
 
-        gpu_canvas.draw_image_rect(image, None, &image_destination, &self.paint);
+        gpu_canvas.draw_image_rect(&image, None, &image_destination, &self.paint);
+        let image_scale = image.width() as f32 / window_size.width as f32;
+        self.draw_scroll_animation(gpu_canvas, &image, image_scale, dt);
 
         self.surface = Some(surface);
         self.cursor_renderer.draw(
@@ -72,7 +72,24 @@ impl Settings {
                     log_to_file = true;
                     false
                 } else {
-                    !(arg.starts_with("--geometry=") || arg == "--wsl")
+                    !(arg.starts_with("--geometry=")
+                        || arg.starts_with("--benchmark-frames=")
+                        || arg.starts_with("--display=")
+                        || arg.starts_with("--present-mode=")
+                        || arg.starts_with("--backend=")
+                        || arg.starts_with("--app-id=")
+                        || arg.starts_with("--x11-class=")
+                        || arg.starts_with("--title=")
+                        || arg.starts_with("--icon=")
+                        || arg == "--vsync"
+                        || arg == "--headless"
+                        || arg == "--x11"
+                        || arg == "--wsl"
+                        || arg == "--transparent"
+                        || arg == "--always-on-top"
+                        || arg == "--frameless"
+                        || arg == "--maximized"
+                        || arg == "--no-resize")
                 }
             })
             .collect::<Vec<String>>();
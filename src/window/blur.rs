@@ -0,0 +1,125 @@
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+// Asks the platform to blur whatever is behind the transparent parts of the window. Only Windows,
+// macOS and KWin on X11 support it, elsewhere an error is returned and the window stays as is.
+pub fn set_background_blur(window: &impl HasRawWindowHandle, enabled: bool) -> Result<(), String> {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Windows(handle) => windows_set_blur(handle.hwnd, enabled),
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::MacOS(handle) => macos_set_blur(handle.ns_window, enabled),
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(handle) => x11_set_blur(handle.display, handle.window, enabled),
+        _ => Err(String::from(
+            "Background blur is not supported by this window system",
+        )),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_set_blur(hwnd: *mut std::ffi::c_void, enabled: bool) -> Result<(), String> {
+    use winapi::shared::windef::HWND;
+    use winapi::um::dwmapi::{DwmEnableBlurBehindWindow, DWM_BB_ENABLE, DWM_BLURBEHIND};
+
+    let blur_behind = DWM_BLURBEHIND {
+        dwFlags: DWM_BB_ENABLE,
+        fEnable: enabled as i32,
+        hRgnBlur: std::ptr::null_mut(),
+        fTransitionOnMaximized: 0,
+    };
+    let result = unsafe { DwmEnableBlurBehindWindow(hwnd as HWND, &blur_behind) };
+    if result < 0 {
+        return Err(format!("DwmEnableBlurBehindWindow failed: {:#x}", result));
+    }
+    Ok(())
+}
+
+// A visual effect view is placed behind the content view, which then shows through wherever the
+// window is transparent
+#[cfg(target_os = "macos")]
+fn macos_set_blur(ns_window: *mut std::ffi::c_void, enabled: bool) -> Result<(), String> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSRect;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    const NS_VIEW_WIDTH_SIZABLE: u64 = 2;
+    const NS_VIEW_HEIGHT_SIZABLE: u64 = 16;
+    const NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW: i64 = 0;
+    const NS_VISUAL_EFFECT_STATE_ACTIVE: i64 = 1;
+    const NS_WINDOW_BELOW: i64 = -1;
+
+    unsafe {
+        let ns_window = ns_window as id;
+        let content_view: id = msg_send![ns_window, contentView];
+
+        let subviews: id = msg_send![content_view, subviews];
+        let subview_count: u64 = msg_send![subviews, count];
+        for index in (0..subview_count).rev() {
+            let subview: id = msg_send![subviews, objectAtIndex: index];
+            let is_effect_view: bool =
+                msg_send![subview, isKindOfClass: class!(NSVisualEffectView)];
+            if is_effect_view {
+                let _: () = msg_send![subview, removeFromSuperview];
+            }
+        }
+
+        if enabled {
+            let bounds: NSRect = msg_send![content_view, bounds];
+            let effect_view: id = msg_send![class!(NSVisualEffectView), alloc];
+            let effect_view: id = msg_send![effect_view, initWithFrame: bounds];
+            let _: () = msg_send![
+                effect_view,
+                setAutoresizingMask: NS_VIEW_WIDTH_SIZABLE | NS_VIEW_HEIGHT_SIZABLE
+            ];
+            let _: () = msg_send![
+                effect_view,
+                setBlendingMode: NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW
+            ];
+            let _: () = msg_send![effect_view, setState: NS_VISUAL_EFFECT_STATE_ACTIVE];
+            let _: () = msg_send![
+                content_view,
+                addSubview: effect_view
+                positioned: NS_WINDOW_BELOW
+                relativeTo: nil
+            ];
+            let _: () = msg_send![effect_view, release];
+        }
+    }
+    Ok(())
+}
+
+// KWin blurs behind any window with this property set. An empty region means the whole window.
+#[cfg(target_os = "linux")]
+fn x11_set_blur(
+    display: *mut std::ffi::c_void,
+    window: std::os::raw::c_ulong,
+    enabled: bool,
+) -> Result<(), String> {
+    use x11::xlib;
+
+    if display.is_null() {
+        return Err(String::from("No X11 display to set background blur on"));
+    }
+
+    unsafe {
+        let display = display as *mut xlib::Display;
+        let atom_name = b"_KDE_NET_WM_BLUR_BEHIND_REGION\0";
+        let atom = xlib::XInternAtom(display, atom_name.as_ptr() as *const _, xlib::False);
+        if enabled {
+            xlib::XChangeProperty(
+                display,
+                window,
+                atom,
+                xlib::XA_CARDINAL,
+                32,
+                xlib::PropModeReplace,
+                std::ptr::null(),
+                0,
+            );
+        } else {
+            xlib::XDeleteProperty(display, window, atom);
+        }
+        xlib::XFlush(display);
+    }
+    Ok(())
+}
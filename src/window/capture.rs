@@ -0,0 +1,57 @@
+use image::ColorType as ImageColorType;
+use rmpv::Value;
+use skulpin::skia_safe::{AlphaType, Canvas, ColorType, ImageInfo};
+
+// A drawn frame read back from the canvas, four bytes of unpremultiplied RGBA per pixel, row by
+// row from the top left
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            ImageColorType::RGBA(8),
+        )
+        .map_err(|error| error.to_string())
+    }
+
+    // The pixels are only included when the frame wasn't written to a file
+    pub fn to_value(&self, path: Option<&str>) -> Value {
+        let mut map = vec![
+            (Value::from("width"), Value::from(self.width)),
+            (Value::from("height"), Value::from(self.height)),
+        ];
+        match path {
+            Some(path) => map.push((Value::from("path"), Value::from(path))),
+            None => map.push((Value::from("pixels"), Value::Binary(self.pixels.clone()))),
+        }
+        Value::Map(map)
+    }
+}
+
+// Reads back what has been drawn so far, at the canvas' physical resolution. With a gpu canvas
+// this waits for the gpu to finish, so it is only done when asked for.
+pub fn capture_canvas(canvas: &mut Canvas) -> Option<CapturedFrame> {
+    let dimensions = canvas.image_info().dimensions();
+    let (width, height) = (dimensions.width as u32, dimensions.height as u32);
+    let image_info = ImageInfo::new(dimensions, ColorType::RGBA8888, AlphaType::Unpremul, None);
+    let row_bytes = width as usize * 4;
+    let mut pixels = vec![0; row_bytes * height as usize];
+    if canvas.read_pixels(&image_info, &mut pixels, row_bytes, (0, 0)) {
+        Some(CapturedFrame {
+            width,
+            height,
+            pixels,
+        })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,471 @@
+use std::sync::Mutex;
+
+use log::{error, trace};
+use rmpv::Value;
+
+use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::window::{parse_urgency, RecordingTarget, Urgency};
+
+lazy_static! {
+    pub static ref WINDOW_COMMANDS: WindowCommandQueue = WindowCommandQueue::new();
+}
+
+// Changes to the window requested from outside the event loop, such as from neovim through
+// rpcnotify(1, 'neovide.set_title', 'Title'). They are applied at the start of the next frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowCommand {
+    SetTitle(String),
+    SetSize(u32, u32),
+    ToggleFullscreen,
+    // Keeps the window's size, centered on the display, and follows it there in fullscreen
+    MoveToDisplay(i32),
+    // Answered with the NeovideDisplays user event once g:neovide_displays holds the list
+    GetDisplays,
+    // Answered with the NeovideFrameStats user event, covering the frames since the last report
+    GetFrameStats,
+    Maximize,
+    Minimize,
+    Restore,
+    SetDecorations(bool),
+    ToggleDecorations,
+    SetTransparency(f32),
+    SetIcon(String),
+    // Strength from 0 to 1 for the duration in milliseconds, on every connected controller
+    Rumble(f32, u32),
+    SetClipboard(String),
+    // Answered with the NeovideClipboard user event once g:neovide_clipboard holds the text
+    GetClipboard,
+    // A system cursor name such as "arrow", "ibeam", "hand" or "resize_horizontal"
+    SetMouseCursor(String),
+    // Reads back the next drawn frame, saved as a PNG at the path if there is one. Answered with
+    // the NeovideFrameCaptured user event once g:neovide_captured_frame describes it.
+    CaptureFrame(Option<String>),
+    // Every Nth drawn frame is recorded until stopped
+    StartRecording(RecordingTarget, u64),
+    StopRecording,
+    // Areas of the grid, as column, row, width and height, that move the window when dragged
+    SetDragRegions(Vec<(u64, u64, u64, u64)>),
+    // Added to the macOS menu bar. Choosing it sends the NeovideMenuItem user event once
+    // g:neovide_menu_item holds the id.
+    AddMenuItem {
+        id: String,
+        title: String,
+        key_equivalent: Option<String>,
+    },
+    // A desktop notification, for letting the user know about finished work while the window
+    // isn't focused
+    Notify {
+        title: String,
+        body: String,
+        urgency: Urgency,
+    },
+    // Four bytes of RGBA for every pixel, row by row, with the hotspot in pixels from the top left
+    SetMouseCursorImage {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+        hotspot: (i32, i32),
+    },
+    // Input handled exactly like the user's, for end to end tests driven from neovim such as in a
+    // --headless instance. Keys go by their SDL name, like Return or Escape. Characters only reach
+    // neovim as text, so they are typed with InjectText.
+    InjectKey(String),
+    InjectText(String),
+    // At a point in logical pixels, with the left, middle or right button
+    InjectClick {
+        x: i32,
+        y: i32,
+        button: String,
+    },
+    InjectScroll(i32, i32),
+}
+
+// Pixel data can be sent as a blob, a string or a list of numbers, since not every client can
+// produce binary messages
+fn pixel_data(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Binary(bytes) => Some(bytes.clone()),
+        Value::String(string) => Some(string.as_bytes().to_vec()),
+        Value::Array(values) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_u64()
+                    .filter(|byte| *byte <= 255)
+                    .map(|byte| byte as u8)
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn drag_region(value: &Value) -> Option<(u64, u64, u64, u64)> {
+    match value.as_array()?.as_slice() {
+        [column, row, width, height] => Some((
+            column.as_u64()?,
+            row.as_u64()?,
+            width.as_u64()?,
+            height.as_u64()?,
+        )),
+        _ => None,
+    }
+}
+
+impl WindowCommand {
+    pub fn parse(event_name: &str, arguments: &[Value]) -> Option<WindowCommand> {
+        let command = match (event_name, arguments) {
+            ("neovide.set_title", [title]) => title
+                .as_str()
+                .map(|title| WindowCommand::SetTitle(title.to_string())),
+            ("neovide.set_size", [width, height]) => width
+                .as_u64()
+                .zip(height.as_u64())
+                .map(|(width, height)| WindowCommand::SetSize(width as u32, height as u32)),
+            ("neovide.toggle_fullscreen", []) => Some(WindowCommand::ToggleFullscreen),
+            ("neovide.move_to_display", [display]) => display
+                .as_i64()
+                .map(|display| WindowCommand::MoveToDisplay(display as i32)),
+            ("neovide.get_displays", []) => Some(WindowCommand::GetDisplays),
+            ("neovide.record_frames", [directory]) => directory.as_str().map(|directory| {
+                WindowCommand::StartRecording(RecordingTarget::Images(directory.to_string()), 1)
+            }),
+            ("neovide.record_frames", [directory, every]) => directory
+                .as_str()
+                .zip(every.as_u64())
+                .map(|(directory, every)| {
+                    WindowCommand::StartRecording(
+                        RecordingTarget::Images(directory.to_string()),
+                        every,
+                    )
+                }),
+            ("neovide.pipe_frames", [command]) => command.as_str().map(|command| {
+                WindowCommand::StartRecording(RecordingTarget::Encoder(command.to_string()), 1)
+            }),
+            ("neovide.pipe_frames", [command, every]) => {
+                command
+                    .as_str()
+                    .zip(every.as_u64())
+                    .map(|(command, every)| {
+                        WindowCommand::StartRecording(
+                            RecordingTarget::Encoder(command.to_string()),
+                            every,
+                        )
+                    })
+            }
+            ("neovide.stop_recording", []) => Some(WindowCommand::StopRecording),
+            ("neovide.capture_frame", []) => Some(WindowCommand::CaptureFrame(None)),
+            ("neovide.capture_frame", [path]) => path
+                .as_str()
+                .map(|path| WindowCommand::CaptureFrame(Some(path.to_string()))),
+            ("neovide.add_menu_item", [id, title]) => {
+                id.as_str()
+                    .zip(title.as_str())
+                    .map(|(id, title)| WindowCommand::AddMenuItem {
+                        id: id.to_string(),
+                        title: title.to_string(),
+                        key_equivalent: None,
+                    })
+            }
+            ("neovide.add_menu_item", [id, title, key_equivalent]) => id
+                .as_str()
+                .zip(title.as_str())
+                .zip(key_equivalent.as_str())
+                .map(|((id, title), key_equivalent)| WindowCommand::AddMenuItem {
+                    id: id.to_string(),
+                    title: title.to_string(),
+                    key_equivalent: Some(key_equivalent.to_string()),
+                }),
+            ("neovide.notify", [title, body]) => {
+                title
+                    .as_str()
+                    .zip(body.as_str())
+                    .map(|(title, body)| WindowCommand::Notify {
+                        title: title.to_string(),
+                        body: body.to_string(),
+                        urgency: Urgency::Normal,
+                    })
+            }
+            ("neovide.notify", [title, body, urgency]) => title
+                .as_str()
+                .zip(body.as_str())
+                .zip(urgency.as_str().and_then(parse_urgency))
+                .map(|((title, body), urgency)| WindowCommand::Notify {
+                    title: title.to_string(),
+                    body: body.to_string(),
+                    urgency,
+                }),
+            ("neovide.inject_key", [name]) => name
+                .as_str()
+                .map(|name| WindowCommand::InjectKey(name.to_string())),
+            ("neovide.inject_text", [text]) => text
+                .as_str()
+                .map(|text| WindowCommand::InjectText(text.to_string())),
+            ("neovide.inject_click", [x, y]) => {
+                x.as_i64()
+                    .zip(y.as_i64())
+                    .map(|(x, y)| WindowCommand::InjectClick {
+                        x: x as i32,
+                        y: y as i32,
+                        button: String::from("left"),
+                    })
+            }
+            ("neovide.inject_click", [x, y, button]) => x
+                .as_i64()
+                .zip(y.as_i64())
+                .zip(button.as_str())
+                .map(|((x, y), button)| WindowCommand::InjectClick {
+                    x: x as i32,
+                    y: y as i32,
+                    button: button.to_string(),
+                }),
+            ("neovide.inject_scroll", [x, y]) => x
+                .as_i64()
+                .zip(y.as_i64())
+                .map(|(x, y)| WindowCommand::InjectScroll(x as i32, y as i32)),
+            ("neovide.get_frame_stats", []) => Some(WindowCommand::GetFrameStats),
+            ("neovide.maximize", []) => Some(WindowCommand::Maximize),
+            ("neovide.minimize", []) => Some(WindowCommand::Minimize),
+            ("neovide.restore", []) => Some(WindowCommand::Restore),
+            ("neovide.set_decorations", [decorations]) => {
+                decorations.as_bool().map(WindowCommand::SetDecorations)
+            }
+            ("neovide.toggle_decorations", []) => Some(WindowCommand::ToggleDecorations),
+            ("neovide.set_transparency", [transparency]) => transparency
+                .as_f64()
+                .map(|transparency| WindowCommand::SetTransparency(transparency as f32)),
+            ("neovide.set_icon", [path]) => path
+                .as_str()
+                .map(|path| WindowCommand::SetIcon(path.to_string())),
+            ("neovide.rumble", [strength, duration]) => strength
+                .as_f64()
+                .zip(duration.as_u64())
+                .map(|(strength, duration)| {
+                    WindowCommand::Rumble(strength as f32, duration as u32)
+                }),
+            ("neovide.set_clipboard", [text]) => text
+                .as_str()
+                .map(|text| WindowCommand::SetClipboard(text.to_string())),
+            ("neovide.get_clipboard", []) => Some(WindowCommand::GetClipboard),
+            ("neovide.set_drag_regions", [regions]) => regions
+                .as_array()
+                .and_then(|regions| regions.iter().map(drag_region).collect())
+                .map(WindowCommand::SetDragRegions),
+            ("neovide.set_mouse_cursor", [name]) => name
+                .as_str()
+                .map(|name| WindowCommand::SetMouseCursor(name.to_string())),
+            ("neovide.set_mouse_cursor_image", [width, height, pixels, hotspot_x, hotspot_y]) => {
+                match (
+                    width.as_u64(),
+                    height.as_u64(),
+                    pixel_data(pixels),
+                    hotspot_x.as_i64(),
+                    hotspot_y.as_i64(),
+                ) {
+                    (Some(width), Some(height), Some(pixels), Some(hotspot_x), Some(hotspot_y))
+                        if pixels.len() as u64 == width * height * 4 =>
+                    {
+                        Some(WindowCommand::SetMouseCursorImage {
+                            width: width as u32,
+                            height: height as u32,
+                            pixels,
+                            hotspot: (hotspot_x as i32, hotspot_y as i32),
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        if command.is_none() {
+            error!(
+                "Unknown window command {} or invalid arguments: {:?}",
+                event_name, arguments
+            );
+        }
+
+        command
+    }
+}
+
+pub struct WindowCommandQueue {
+    commands: Mutex<Vec<WindowCommand>>,
+}
+
+impl WindowCommandQueue {
+    pub fn new() -> WindowCommandQueue {
+        WindowCommandQueue {
+            commands: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn queue(&self, command: WindowCommand) {
+        trace!("Window command queued: {:?}", command);
+        self.commands.lock().unwrap().push(command);
+        REDRAW_SCHEDULER.wake();
+    }
+
+    pub fn drain(&self) -> Vec<WindowCommand> {
+        std::mem::take(&mut *self.commands.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window_commands() {
+        assert_eq!(
+            WindowCommand::parse("neovide.set_title", &[Value::from("Notes")]),
+            Some(WindowCommand::SetTitle(String::from("Notes")))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_size", &[Value::from(800), Value::from(600)]),
+            Some(WindowCommand::SetSize(800, 600))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.toggle_fullscreen", &[]),
+            Some(WindowCommand::ToggleFullscreen)
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_transparency", &[Value::from(0.5)]),
+            Some(WindowCommand::SetTransparency(0.5))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.rumble", &[Value::from(0.5), Value::from(200)]),
+            Some(WindowCommand::Rumble(0.5, 200))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_clipboard", &[Value::from("copied")]),
+            Some(WindowCommand::SetClipboard(String::from("copied")))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.get_clipboard", &[]),
+            Some(WindowCommand::GetClipboard)
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_mouse_cursor", &[Value::from("ibeam")]),
+            Some(WindowCommand::SetMouseCursor(String::from("ibeam")))
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.set_mouse_cursor_image",
+                &[
+                    Value::from(1),
+                    Value::from(1),
+                    Value::Array(vec![
+                        Value::from(255),
+                        Value::from(0),
+                        Value::from(0),
+                        Value::from(255)
+                    ]),
+                    Value::from(0),
+                    Value::from(0)
+                ]
+            ),
+            Some(WindowCommand::SetMouseCursorImage {
+                width: 1,
+                height: 1,
+                pixels: vec![255, 0, 0, 255],
+                hotspot: (0, 0)
+            })
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.set_mouse_cursor_image",
+                &[
+                    Value::from(2),
+                    Value::from(2),
+                    Value::Binary(vec![0; 4]),
+                    Value::from(0),
+                    Value::from(0)
+                ]
+            ),
+            None
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.set_drag_regions",
+                &[Value::Array(vec![Value::Array(vec![
+                    Value::from(0),
+                    Value::from(0),
+                    Value::from(80),
+                    Value::from(1)
+                ])])]
+            ),
+            Some(WindowCommand::SetDragRegions(vec![(0, 0, 80, 1)]))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_decorations", &[Value::from(false)]),
+            Some(WindowCommand::SetDecorations(false))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.move_to_display", &[Value::from(1)]),
+            Some(WindowCommand::MoveToDisplay(1))
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.get_frame_stats", &[]),
+            Some(WindowCommand::GetFrameStats)
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.capture_frame", &[Value::from("frame.png")]),
+            Some(WindowCommand::CaptureFrame(Some(String::from("frame.png"))))
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.record_frames",
+                &[Value::from("frames"), Value::from(2)]
+            ),
+            Some(WindowCommand::StartRecording(
+                RecordingTarget::Images(String::from("frames")),
+                2
+            ))
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.add_menu_item",
+                &[Value::from("build"), Value::from("Build"), Value::from("b")]
+            ),
+            Some(WindowCommand::AddMenuItem {
+                id: String::from("build"),
+                title: String::from("Build"),
+                key_equivalent: Some(String::from("b")),
+            })
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.notify",
+                &[
+                    Value::from("Build"),
+                    Value::from("Finished"),
+                    Value::from("low")
+                ]
+            ),
+            Some(WindowCommand::Notify {
+                title: String::from("Build"),
+                body: String::from("Finished"),
+                urgency: Urgency::Low,
+            })
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.inject_key", &[Value::from("Escape")]),
+            Some(WindowCommand::InjectKey(String::from("Escape")))
+        );
+        assert_eq!(
+            WindowCommand::parse(
+                "neovide.inject_click",
+                &[Value::from(10), Value::from(20), Value::from("right")]
+            ),
+            Some(WindowCommand::InjectClick {
+                x: 10,
+                y: 20,
+                button: String::from("right"),
+            })
+        );
+        assert_eq!(
+            WindowCommand::parse("neovide.set_size", &[Value::from(800)]),
+            None
+        );
+    }
+}
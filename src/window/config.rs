@@ -0,0 +1,169 @@
+use skulpin::PresentMode;
+
+// The graphics API the window draws with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RenderingBackend {
+    Vulkan,
+    OpenGl,
+    Software,
+}
+
+// Options fixed when the window is created. Everything that can change while running is a setting
+// instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    // In grid cells rather than pixels, since the pixel size depends on the font
+    pub initial_size: Option<(u64, u64)>,
+    pub resizable: bool,
+    pub transparent: bool,
+    pub maximized: bool,
+    pub always_on_top: bool,
+    // Without a title bar or border. Neovim can mark parts of the grid as draggable instead.
+    pub frameless: bool,
+    // Index of the display the window opens centered on, instead of the one SDL picks
+    pub display: Option<i32>,
+    // Tried in order until the swapchain supports one. Fifo waits for vsync, Mailbox doesn't tear
+    // but renders every frame, and Immediate has the lowest latency at the cost of tearing.
+    pub present_modes: Vec<PresentMode>,
+    // Without one, Vulkan, OpenGL and then software rendering are tried until one works
+    pub backend: Option<RenderingBackend>,
+    // No OS window is shown, and frames are drawn in memory with the software backend. SDL's
+    // dummy video driver is used, so it runs without a display server. Set with --headless, and
+    // driven from neovim with the neovide.inject_* commands.
+    pub headless: bool,
+    // Uses SDL's native Wayland driver instead of going through XWayland, which scales the window
+    // as a blurry bitmap. Falls back to X11 if the Wayland driver can't start.
+    pub wayland: bool,
+    // Lets window managers and docks match the window to its desktop file. Used as the Wayland app
+    // id, and as both the X11 instance and class names unless x11_class is set.
+    pub app_id: Option<String>,
+    // X11 WM_CLASS instance and class names, for window rules that tell the two apart
+    pub x11_class: Option<(String, String)>,
+    // Encoded image data in any format the image crate reads. Neovide's own icon is used without it.
+    pub icon: Option<Vec<u8>>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: String::from("Neovide"),
+            initial_size: None,
+            resizable: true,
+            transparent: false,
+            maximized: false,
+            always_on_top: false,
+            frameless: false,
+            display: None,
+            present_modes: vec![PresentMode::Immediate],
+            backend: None,
+            headless: false,
+            wayland: false,
+            app_id: None,
+            x11_class: None,
+            icon: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn new() -> WindowConfig {
+        WindowConfig::default()
+    }
+
+    pub fn title(mut self, title: &str) -> WindowConfig {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn initial_size(mut self, width: u64, height: u64) -> WindowConfig {
+        self.initial_size = Some((width, height));
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> WindowConfig {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn transparent(mut self, transparent: bool) -> WindowConfig {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn maximized(mut self, maximized: bool) -> WindowConfig {
+        self.maximized = maximized;
+        self
+    }
+
+    pub fn always_on_top(mut self, always_on_top: bool) -> WindowConfig {
+        self.always_on_top = always_on_top;
+        self
+    }
+
+    pub fn frameless(mut self, frameless: bool) -> WindowConfig {
+        self.frameless = frameless;
+        self
+    }
+
+    pub fn display(mut self, display: i32) -> WindowConfig {
+        self.display = Some(display);
+        self
+    }
+
+    pub fn present_modes(mut self, present_modes: Vec<PresentMode>) -> WindowConfig {
+        self.present_modes = present_modes;
+        self
+    }
+
+    pub fn backend(mut self, backend: RenderingBackend) -> WindowConfig {
+        self.backend = Some(backend);
+        self
+    }
+
+    pub fn headless(mut self, headless: bool) -> WindowConfig {
+        self.headless = headless;
+        self
+    }
+
+    pub fn wayland(mut self, wayland: bool) -> WindowConfig {
+        self.wayland = wayland;
+        self
+    }
+
+    pub fn app_id(mut self, app_id: &str) -> WindowConfig {
+        self.app_id = Some(app_id.to_string());
+        self
+    }
+
+    pub fn x11_class(mut self, instance: &str, class: &str) -> WindowConfig {
+        self.x11_class = Some((instance.to_string(), class.to_string()));
+        self
+    }
+
+    pub fn icon(mut self, icon: Vec<u8>) -> WindowConfig {
+        self.icon = Some(icon);
+        self
+    }
+}
+
+// Names accepted by --present-mode, with vsync as another name for fifo
+pub fn parse_present_mode(name: &str) -> Option<PresentMode> {
+    match name.trim().to_lowercase().as_str() {
+        "immediate" => Some(PresentMode::Immediate),
+        "mailbox" => Some(PresentMode::Mailbox),
+        "fifo" | "vsync" => Some(PresentMode::Fifo),
+        "fifo_relaxed" => Some(PresentMode::FifoRelaxed),
+        _ => None,
+    }
+}
+
+// Names accepted by --backend
+pub fn parse_rendering_backend(name: &str) -> Option<RenderingBackend> {
+    match name.trim().to_lowercase().as_str() {
+        "vulkan" => Some(RenderingBackend::Vulkan),
+        "opengl" | "gl" => Some(RenderingBackend::OpenGl),
+        "software" | "cpu" => Some(RenderingBackend::Software),
+        _ => None,
+    }
+}
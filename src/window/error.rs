@@ -0,0 +1,26 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum WindowError {
+    Sdl(String),
+    VideoSubsystem(String),
+    Window(String),
+    Renderer(String),
+    EventPump(String),
+}
+
+impl fmt::Display for WindowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowError::Sdl(error) => write!(f, "Could not initialize sdl: {}", error),
+            WindowError::VideoSubsystem(error) => {
+                write!(f, "Could not create sdl video subsystem: {}", error)
+            }
+            WindowError::Window(error) => write!(f, "Could not create window: {}", error),
+            WindowError::Renderer(error) => write!(f, "Could not create renderer: {}", error),
+            WindowError::EventPump(error) => {
+                write!(f, "Could not create sdl event pump: {}", error)
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use log::{error, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FullscreenMode {
+    Desktop { display: i32 },
+}
+
+// The size and position are always the windowed ones, so a window restored into fullscreen or
+// maximized still has somewhere sensible to return to when it leaves that state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowGeometry {
+    pub size: (u32, u32),
+    pub position: (i32, i32),
+    pub maximized: bool,
+    pub fullscreen: Option<FullscreenMode>,
+}
+
+impl fmt::Display for WindowGeometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (width, height) = self.size;
+        let (x, y) = self.position;
+        write!(f, "{}x{}+{}+{}", width, height, x, y)?;
+
+        if self.maximized {
+            write!(f, " maximized")?;
+        }
+
+        if let Some(FullscreenMode::Desktop { display }) = self.fullscreen {
+            write!(f, " fullscreen={}", display)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for WindowGeometry {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid window geometry: {}", input);
+        let mut parts = input.split_whitespace();
+
+        let bounds = parts
+            .next()
+            .ok_or_else(invalid)?
+            .split(|c| c == 'x' || c == '+')
+            .collect::<Vec<_>>();
+        let (size, position) = if let [width, height, x, y] = bounds[..] {
+            (
+                (
+                    width.parse().map_err(|_| invalid())?,
+                    height.parse().map_err(|_| invalid())?,
+                ),
+                (
+                    x.parse().map_err(|_| invalid())?,
+                    y.parse().map_err(|_| invalid())?,
+                ),
+            )
+        } else {
+            return Err(invalid());
+        };
+
+        let mut maximized = false;
+        let mut fullscreen = None;
+        for part in parts {
+            if part == "maximized" {
+                maximized = true;
+            } else if let Some(display) = part.strip_prefix("fullscreen=") {
+                let display = display.parse().map_err(|_| invalid())?;
+                fullscreen = Some(FullscreenMode::Desktop { display });
+            } else {
+                return Err(invalid());
+            }
+        }
+
+        Ok(WindowGeometry {
+            size,
+            position,
+            maximized,
+            fullscreen,
+        })
+    }
+}
+
+fn geometry_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|data_dir| data_dir.join("neovide").join("window_geometry"))
+}
+
+pub fn load_window_geometry() -> Option<WindowGeometry> {
+    let contents = fs::read_to_string(geometry_path()?).ok()?;
+    contents
+        .trim()
+        .parse()
+        .map_err(|error| warn!("Ignoring saved window geometry: {}", error))
+        .ok()
+}
+
+pub fn save_window_geometry(geometry: &WindowGeometry) {
+    if let Some(path) = geometry_path() {
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, geometry.to_string()));
+
+        if let Err(error) = result {
+            error!("Could not save window geometry: {}", error);
+        }
+    }
+}
+
+pub fn forget_window_geometry() {
+    if let Some(path) = geometry_path() {
+        fs::remove_file(path).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windowed_geometry_round_trip() {
+        let geometry = WindowGeometry {
+            size: (800, 600),
+            position: (-10, 20),
+            maximized: false,
+            fullscreen: None,
+        };
+
+        let serialized = geometry.to_string();
+        assert_eq!(serialized, "800x600+-10+20");
+        assert_eq!(serialized.parse::<WindowGeometry>(), Ok(geometry));
+    }
+
+    #[test]
+    fn test_fullscreen_geometry_round_trip() {
+        let geometry = WindowGeometry {
+            size: (1024, 768),
+            position: (100, 50),
+            maximized: false,
+            fullscreen: Some(FullscreenMode::Desktop { display: 1 }),
+        };
+
+        let serialized = geometry.to_string();
+        assert_eq!(serialized, "1024x768+100+50 fullscreen=1");
+        assert_eq!(serialized.parse::<WindowGeometry>(), Ok(geometry));
+    }
+
+    #[test]
+    fn test_maximized_geometry_round_trip() {
+        let geometry = WindowGeometry {
+            size: (800, 600),
+            position: (0, 0),
+            maximized: true,
+            fullscreen: None,
+        };
+
+        let serialized = geometry.to_string();
+        assert_eq!(serialized, "800x600+0+0 maximized");
+        assert_eq!(serialized.parse::<WindowGeometry>(), Ok(geometry));
+    }
+
+    #[test]
+    fn test_invalid_geometry() {
+        assert!("800x600".parse::<WindowGeometry>().is_err());
+        assert!("800x600+0+0 minimized".parse::<WindowGeometry>().is_err());
+    }
+}
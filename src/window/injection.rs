@@ -0,0 +1,220 @@
+use std::sync::Mutex;
+
+use skulpin::sdl2::event::Event;
+use skulpin::sdl2::keyboard::{Keycode, Mod, Scancode};
+use skulpin::sdl2::mouse::{MouseButton, MouseState, MouseWheelDirection};
+
+use crate::redraw_scheduler::REDRAW_SCHEDULER;
+
+lazy_static! {
+    static ref INJECTED_EVENTS: Mutex<Vec<InjectedEvent>> = Mutex::new(Vec::new());
+}
+
+// Input that didn't come from the user, for end to end tests. Unlike SDL's own events these can
+// be sent between threads. Coordinates are logical pixels from the top left of the window.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InjectedEvent {
+    KeyDown {
+        keycode: Keycode,
+        keymod: Mod,
+    },
+    KeyUp {
+        keycode: Keycode,
+        keymod: Mod,
+    },
+    TextInput(String),
+    MouseMotion {
+        x: i32,
+        y: i32,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+        x: i32,
+        y: i32,
+    },
+    MouseWheel {
+        x: i32,
+        y: i32,
+    },
+}
+
+impl InjectedEvent {
+    // Handled exactly like the real thing once in the loop
+    pub fn into_sdl_event(self, window_id: u32) -> Event {
+        let timestamp = 0;
+        match self {
+            InjectedEvent::KeyDown { keycode, keymod } => Event::KeyDown {
+                timestamp,
+                window_id,
+                keycode: Some(keycode),
+                scancode: Scancode::from_keycode(keycode),
+                keymod,
+                repeat: false,
+            },
+            InjectedEvent::KeyUp { keycode, keymod } => Event::KeyUp {
+                timestamp,
+                window_id,
+                keycode: Some(keycode),
+                scancode: Scancode::from_keycode(keycode),
+                keymod,
+                repeat: false,
+            },
+            InjectedEvent::TextInput(text) => Event::TextInput {
+                timestamp,
+                window_id,
+                text,
+            },
+            InjectedEvent::MouseMotion { x, y } => Event::MouseMotion {
+                timestamp,
+                window_id,
+                which: 0,
+                mousestate: MouseState::from_sdl_state(0),
+                x,
+                y,
+                xrel: 0,
+                yrel: 0,
+            },
+            InjectedEvent::MouseButton {
+                button,
+                pressed: true,
+                x,
+                y,
+            } => Event::MouseButtonDown {
+                timestamp,
+                window_id,
+                which: 0,
+                mouse_btn: button,
+                clicks: 1,
+                x,
+                y,
+            },
+            InjectedEvent::MouseButton {
+                button,
+                pressed: false,
+                x,
+                y,
+            } => Event::MouseButtonUp {
+                timestamp,
+                window_id,
+                which: 0,
+                mouse_btn: button,
+                clicks: 1,
+                x,
+                y,
+            },
+            InjectedEvent::MouseWheel { x, y } => Event::MouseWheel {
+                timestamp,
+                window_id,
+                which: 0,
+                x,
+                y,
+                direction: MouseWheelDirection::Normal,
+            },
+        }
+    }
+}
+
+// A handle for feeding the loop from any thread. Events are picked up at the start of the next
+// frame, before the ones SDL delivered. Neovim reaches it through the neovide.inject_* commands.
+#[derive(Clone, Debug, Default)]
+pub struct EventInjector;
+
+impl EventInjector {
+    pub fn new() -> EventInjector {
+        EventInjector
+    }
+
+    pub fn inject(&self, event: InjectedEvent) {
+        INJECTED_EVENTS.lock().unwrap().push(event);
+        REDRAW_SCHEDULER.wake();
+    }
+
+    // Presses and releases a key by its SDL name, such as Return or Escape
+    pub fn inject_key(&self, name: &str) -> Result<(), String> {
+        let keycode =
+            Keycode::from_name(name).ok_or_else(|| format!("Unknown key name {}", name))?;
+        self.inject(InjectedEvent::KeyDown {
+            keycode,
+            keymod: Mod::NOMOD,
+        });
+        self.inject(InjectedEvent::KeyUp {
+            keycode,
+            keymod: Mod::NOMOD,
+        });
+        Ok(())
+    }
+
+    // Moves the mouse to the point first, like a real click would
+    pub fn inject_click(&self, x: i32, y: i32, button: &str) -> Result<(), String> {
+        let button = match button {
+            "left" => MouseButton::Left,
+            "middle" => MouseButton::Middle,
+            "right" => MouseButton::Right,
+            _ => return Err(format!("Unknown mouse button {}", button)),
+        };
+        self.inject(InjectedEvent::MouseMotion { x, y });
+        for &pressed in &[true, false] {
+            self.inject(InjectedEvent::MouseButton {
+                button,
+                pressed,
+                x,
+                y,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub fn drain_injected_events() -> Vec<InjectedEvent> {
+    std::mem::take(&mut *INJECTED_EVENTS.lock().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_injected_key_has_scancode() {
+        let event = InjectedEvent::KeyDown {
+            keycode: Keycode::A,
+            keymod: Mod::NOMOD,
+        }
+        .into_sdl_event(1);
+
+        match event {
+            Event::KeyDown {
+                window_id,
+                keycode,
+                scancode,
+                ..
+            } => {
+                assert_eq!(window_id, 1);
+                assert_eq!(keycode, Some(Keycode::A));
+                assert_eq!(scancode, Some(Scancode::A));
+            }
+            _ => panic!("Expected a key down event"),
+        }
+    }
+
+    #[test]
+    fn test_injected_key_is_pressed_and_released() {
+        let injector = EventInjector::new();
+        injector.inject_key("Return").unwrap();
+        assert!(injector.inject_key("Not a key").is_err());
+
+        assert_eq!(
+            drain_injected_events(),
+            vec![
+                InjectedEvent::KeyDown {
+                    keycode: Keycode::Return,
+                    keymod: Mod::NOMOD,
+                },
+                InjectedEvent::KeyUp {
+                    keycode: Keycode::Return,
+                    keymod: Mod::NOMOD,
+                },
+            ]
+        );
+    }
+}
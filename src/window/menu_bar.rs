@@ -0,0 +1,188 @@
+// The native menu bar on macOS, with the usual App, Edit and Window menus and a menu of items
+// added from neovim. Choosing one of those sends the NeovideMenuItem user event with its id. The
+// menu bar only exists on macOS, elsewhere installing it does nothing and adding items fails.
+#[cfg(target_os = "macos")]
+pub use macos::*;
+
+#[cfg(not(target_os = "macos"))]
+pub fn install_menu_bar() {}
+
+#[cfg(not(target_os = "macos"))]
+pub fn add_menu_item(_id: &str, _title: &str, _key_equivalent: Option<&str>) -> Result<(), String> {
+    Err(String::from("There is no menu bar on this platform"))
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+    use std::sync::Once;
+
+    use cocoa::appkit::{NSApp, NSApplication, NSEventModifierFlags, NSMenu, NSMenuItem};
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSAutoreleasePool, NSString};
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    use crate::bridge::{UiCommand, BRIDGE};
+
+    const CUSTOM_MENU_TITLE: &str = "Commands";
+
+    // Menu items only hold a weak reference to their target, so the actions are class methods and
+    // the class itself, which is never freed, is the target
+    fn menu_target() -> id {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            let mut decl = ClassDecl::new("NeovideMenuTarget", class!(NSObject))
+                .expect("Could not declare the menu target class");
+            unsafe {
+                decl.add_class_method(
+                    sel!(menuItemSelected:),
+                    menu_item_selected as extern "C" fn(&Class, Sel, id),
+                );
+                decl.add_class_method(sel!(sendKeys:), send_keys as extern "C" fn(&Class, Sel, id));
+            }
+            decl.register();
+        });
+        class!(NeovideMenuTarget) as *const Class as id
+    }
+
+    extern "C" fn menu_item_selected(_: &Class, _: Sel, item: id) {
+        if let Some(id) = unsafe { represented_string(item) } {
+            BRIDGE.queue_command(UiCommand::MenuItem(id));
+        }
+    }
+
+    // The Edit menu acts as if its shortcut was pressed, so it follows the user's own mappings
+    extern "C" fn send_keys(_: &Class, _: Sel, item: id) {
+        if let Some(keys) = unsafe { represented_string(item) } {
+            BRIDGE.queue_command(UiCommand::Keyboard(keys));
+        }
+    }
+
+    unsafe fn represented_string(item: id) -> Option<String> {
+        let object: id = msg_send![item, representedObject];
+        if object == nil {
+            return None;
+        }
+        let bytes: *const c_char = msg_send![object, UTF8String];
+        Some(CStr::from_ptr(bytes).to_string_lossy().into_owned())
+    }
+
+    unsafe fn ns_string(string: &str) -> id {
+        NSString::alloc(nil).init_str(string).autorelease()
+    }
+
+    unsafe fn add_item(menu: id, title: &str, action: Sel, key_equivalent: &str) -> id {
+        let item = NSMenuItem::alloc(nil)
+            .initWithTitle_action_keyEquivalent_(
+                ns_string(title),
+                action,
+                ns_string(key_equivalent),
+            )
+            .autorelease();
+        menu.addItem_(item);
+        item
+    }
+
+    // Keys are left without shortcuts so they keep reaching neovim as key presses
+    unsafe fn add_key_item(menu: id, title: &str, keys: &str) {
+        let item = add_item(menu, title, sel!(sendKeys:), "");
+        let _: () = msg_send![item, setTarget: menu_target()];
+        let _: () = msg_send![item, setRepresentedObject: ns_string(keys)];
+    }
+
+    unsafe fn add_submenu(menu_bar: id, title: &str) -> id {
+        let menu: id = msg_send![NSMenu::alloc(nil), initWithTitle: ns_string(title)];
+        let item = NSMenuItem::new(nil).autorelease();
+        let _: () = msg_send![item, setTitle: ns_string(title)];
+        item.setSubmenu_(menu.autorelease());
+        menu_bar.addItem_(item);
+        menu
+    }
+
+    // Replaces the minimal menu SDL sets up. Has to run on the main thread once SDL has started.
+    pub fn install_menu_bar() {
+        unsafe {
+            let _pool = NSAutoreleasePool::new(nil);
+            let app = NSApp();
+            let menu_bar = NSMenu::new(nil).autorelease();
+
+            let app_menu = add_submenu(menu_bar, "Neovide");
+            add_item(
+                app_menu,
+                "About Neovide",
+                sel!(orderFrontStandardAboutPanel:),
+                "",
+            );
+            app_menu.addItem_(NSMenuItem::separatorItem(nil));
+            add_item(app_menu, "Hide Neovide", sel!(hide:), "h");
+            let hide_others = add_item(app_menu, "Hide Others", sel!(hideOtherApplications:), "h");
+            hide_others.setKeyEquivalentModifierMask_(
+                NSEventModifierFlags::NSCommandKeyMask | NSEventModifierFlags::NSAlternateKeyMask,
+            );
+            add_item(app_menu, "Show All", sel!(unhideAllApplications:), "");
+            app_menu.addItem_(NSMenuItem::separatorItem(nil));
+            // SDL turns terminate: into a quit event, so confirm_quit still applies
+            add_item(app_menu, "Quit Neovide", sel!(terminate:), "q");
+
+            let edit_menu = add_submenu(menu_bar, "Edit");
+            add_key_item(edit_menu, "Undo", "<D-z>");
+            add_key_item(edit_menu, "Redo", "<D-Z>");
+            edit_menu.addItem_(NSMenuItem::separatorItem(nil));
+            add_key_item(edit_menu, "Cut", "<D-x>");
+            add_key_item(edit_menu, "Copy", "<D-c>");
+            add_key_item(edit_menu, "Paste", "<D-v>");
+            add_key_item(edit_menu, "Select All", "<D-a>");
+
+            let window_menu = add_submenu(menu_bar, "Window");
+            add_item(window_menu, "Minimize", sel!(performMiniaturize:), "m");
+            add_item(window_menu, "Zoom", sel!(performZoom:), "");
+            window_menu.addItem_(NSMenuItem::separatorItem(nil));
+            add_item(window_menu, "Bring All to Front", sel!(arrangeInFront:), "");
+
+            app.setMainMenu_(menu_bar);
+            let _: () = msg_send![app, setWindowsMenu: window_menu];
+        }
+    }
+
+    // The key equivalent is used with Command, so "k" is Cmd+K
+    pub fn add_menu_item(
+        id: &str,
+        title: &str,
+        key_equivalent: Option<&str>,
+    ) -> Result<(), String> {
+        unsafe {
+            let menu_bar: id = msg_send![NSApp(), mainMenu];
+            if menu_bar == nil {
+                return Err(String::from("The menu bar has not been installed"));
+            }
+
+            let custom_item: id = msg_send![menu_bar, itemWithTitle: ns_string(CUSTOM_MENU_TITLE)];
+            let custom_menu: id = if custom_item == nil {
+                // Kept before the Window menu, which macOS expects to be last
+                let menu = add_submenu(menu_bar, CUSTOM_MENU_TITLE);
+                let count: i64 = msg_send![menu_bar, numberOfItems];
+                let item: id = msg_send![menu_bar, itemAtIndex: count - 1];
+                let _: () = msg_send![item, retain];
+                let _: () = msg_send![menu_bar, removeItem: item];
+                let _: () = msg_send![menu_bar, insertItem: item atIndex: count - 2];
+                let _: () = msg_send![item, release];
+                menu
+            } else {
+                msg_send![custom_item, submenu]
+            };
+
+            let item = add_item(
+                custom_menu,
+                title,
+                sel!(menuItemSelected:),
+                key_equivalent.unwrap_or(""),
+            );
+            let _: () = msg_send![item, setTarget: menu_target()];
+            let _: () = msg_send![item, setRepresentedObject: ns_string(id)];
+        }
+        Ok(())
+    }
+}
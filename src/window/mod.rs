@@ -2,4 +2,55 @@
 #[cfg_attr(feature = "winit", path = "winit.rs")]
 mod window_wrapper;
 
+#[cfg(feature = "sdl2")]
+mod blur;
+mod capture;
+mod commands;
+mod config;
+mod error;
+mod geometry;
+#[cfg(feature = "sdl2")]
+mod injection;
+#[cfg(feature = "sdl2")]
+mod menu_bar;
+#[cfg(feature = "sdl2")]
+mod native_fullscreen;
+mod notification;
+#[cfg(feature = "sdl2")]
+mod opengl;
+mod recording;
+#[cfg(feature = "sdl2")]
+mod software;
+#[cfg(feature = "sdl2")]
+mod title_bar;
+#[cfg(feature = "sdl2")]
+mod tray;
+#[cfg(feature = "sdl2")]
+mod wm_class;
+
+#[cfg(feature = "sdl2")]
+pub use blur::*;
+pub use capture::*;
+pub use commands::*;
+pub use config::*;
+pub use error::*;
+pub use geometry::*;
+#[cfg(feature = "sdl2")]
+pub use injection::*;
+#[cfg(feature = "sdl2")]
+pub use menu_bar::*;
+#[cfg(feature = "sdl2")]
+pub use native_fullscreen::*;
+pub use notification::*;
+#[cfg(feature = "sdl2")]
+pub use opengl::*;
+pub use recording::*;
+#[cfg(feature = "sdl2")]
+pub use software::*;
+#[cfg(feature = "sdl2")]
+pub use title_bar::*;
+#[cfg(feature = "sdl2")]
+pub use tray::*;
 pub use window_wrapper::*;
+#[cfg(feature = "sdl2")]
+pub use wm_class::*;
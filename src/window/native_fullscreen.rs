@@ -0,0 +1,44 @@
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+// Enters or leaves fullscreen the way the green title bar button does, in a Space of its own with
+// the usual animation. Only macOS has this, elsewhere an error is returned.
+pub fn toggle_native_fullscreen(window: &impl HasRawWindowHandle) -> Result<(), String> {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::MacOS(handle) => {
+            use cocoa::base::{id, nil};
+            use objc::{msg_send, sel, sel_impl};
+
+            if handle.ns_window.is_null() {
+                return Err(String::from("No NSWindow to make fullscreen"));
+            }
+            unsafe {
+                let _: () = msg_send![handle.ns_window as id, toggleFullScreen: nil];
+            }
+            Ok(())
+        }
+        _ => Err(String::from(
+            "Native fullscreen is not supported by this window system",
+        )),
+    }
+}
+
+// True while the window is in a fullscreen Space, however it got there
+pub fn is_native_fullscreen(window: &impl HasRawWindowHandle) -> bool {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::MacOS(handle) => {
+            use cocoa::base::id;
+            use objc::{msg_send, sel, sel_impl};
+
+            const NS_WINDOW_STYLE_MASK_FULL_SCREEN: u64 = 1 << 14;
+
+            if handle.ns_window.is_null() {
+                return false;
+            }
+            let style_mask: u64 = unsafe { msg_send![handle.ns_window as id, styleMask] };
+            style_mask & NS_WINDOW_STYLE_MASK_FULL_SCREEN != 0
+        }
+        _ => false,
+    }
+}
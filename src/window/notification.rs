@@ -0,0 +1,85 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Urgency {
+    Low,
+    Normal,
+    // Stays on screen until dismissed where the platform allows it
+    Critical,
+}
+
+pub fn parse_urgency(name: &str) -> Option<Urgency> {
+    match name.trim().to_lowercase().as_str() {
+        "low" => Some(Urgency::Low),
+        "normal" => Some(Urgency::Normal),
+        "critical" => Some(Urgency::Critical),
+        _ => None,
+    }
+}
+
+// Shows a desktop notification through notify-send, which every notification daemon supports
+// over DBus. It runs on a thread of its own so a slow daemon doesn't hold up the window.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn show_notification(title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+    use log::warn;
+    use std::process::Command;
+
+    let urgency = match urgency {
+        Urgency::Low => "low",
+        Urgency::Normal => "normal",
+        Urgency::Critical => "critical",
+    };
+    let mut child = Command::new("notify-send")
+        .arg("--app-name=Neovide")
+        .arg(format!("--urgency={}", urgency))
+        .arg("--")
+        .arg(title)
+        .arg(body)
+        .spawn()
+        .map_err(|error| format!("Could not run notify-send: {}", error))?;
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => warn!("notify-send failed: {}", status),
+        Err(error) => warn!("notify-send failed: {}", error),
+        _ => {}
+    });
+    Ok(())
+}
+
+// NSUserNotificationCenter is only available to apps started from a bundle
+#[cfg(target_os = "macos")]
+pub fn show_notification(title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    #[link(name = "Foundation", kind = "framework")]
+    extern "C" {
+        static NSUserNotificationDefaultSoundName: id;
+    }
+
+    unsafe {
+        let center: id = msg_send![
+            class!(NSUserNotificationCenter),
+            defaultUserNotificationCenter
+        ];
+        if center == nil {
+            return Err(String::from(
+                "Notifications are only available when Neovide runs from its app bundle",
+            ));
+        }
+
+        let notification: id = msg_send![class!(NSUserNotification), alloc];
+        let notification: id = msg_send![notification, init];
+        let title = NSString::alloc(nil).init_str(title);
+        let body = NSString::alloc(nil).init_str(body);
+        let _: () = msg_send![notification, setTitle: title];
+        let _: () = msg_send![notification, setInformativeText: body];
+        if urgency == Urgency::Critical {
+            let _: () = msg_send![notification, setSoundName: NSUserNotificationDefaultSoundName];
+        }
+        let _: () = msg_send![center, deliverNotification: notification];
+
+        let _: () = msg_send![title, release];
+        let _: () = msg_send![body, release];
+        let _: () = msg_send![notification, release];
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use log::warn;
+use skulpin::sdl2::video::{GLContext, GLProfile, SwapInterval, VideoSubsystem, Window};
+use skulpin::skia_safe::gpu::{gl::FramebufferInfo, BackendRenderTarget, Context, SurfaceOrigin};
+use skulpin::skia_safe::{Canvas, ColorType, Surface};
+
+// GL_RGBA8, the format of the default framebuffer SDL creates
+const FRAMEBUFFER_FORMAT: u32 = 0x8058;
+
+// Has to be called before the window is created, since SDL picks the pixel format from these
+pub fn set_gl_attributes(video_subsystem: &VideoSubsystem) {
+    let gl_attr = video_subsystem.gl_attr();
+    gl_attr.set_context_profile(GLProfile::Core);
+    gl_attr.set_context_version(3, 3);
+    gl_attr.set_stencil_size(8);
+    gl_attr.set_double_buffer(true);
+}
+
+// Draws with Skia's OpenGL backend into the window's default framebuffer. Used where there is no
+// working Vulkan driver, such as on older Intel GPUs and in virtual machines.
+pub struct OpenGlRenderer {
+    // Dropping the context deletes it, so it is kept for as long as the renderer
+    gl_context: GLContext,
+    context: Context,
+    surface: Option<((u32, u32), Surface)>,
+}
+
+impl OpenGlRenderer {
+    pub fn new(window: &Window, vsync: bool) -> Result<OpenGlRenderer, String> {
+        let gl_context = window.gl_create_context()?;
+        window.gl_make_current(&gl_context)?;
+
+        let swap_interval = if vsync {
+            SwapInterval::VSync
+        } else {
+            SwapInterval::Immediate
+        };
+        if let Err(error) = window.subsystem().gl_set_swap_interval(swap_interval) {
+            warn!("Could not set the OpenGL swap interval: {}", error);
+        }
+
+        let context = Context::new_gl(None)
+            .ok_or_else(|| String::from("Could not create a Skia OpenGL context"))?;
+
+        Ok(OpenGlRenderer {
+            gl_context,
+            context,
+            surface: None,
+        })
+    }
+
+    pub fn draw(&mut self, window: &Window, draw: impl FnOnce(&mut Canvas)) -> Result<(), String> {
+        window.gl_make_current(&self.gl_context)?;
+
+        // The surface wraps the framebuffer at a fixed size, so it is made again on resize
+        let size = window.drawable_size();
+        if self
+            .surface
+            .as_ref()
+            .map_or(true, |(surface_size, _)| *surface_size != size)
+        {
+            let surface = self.create_surface(size)?;
+            self.surface = Some((size, surface));
+        }
+        let (_, surface) = self.surface.as_mut().unwrap();
+
+        let (logical_width, _) = window.size();
+        let scale_factor = size.0 as f32 / logical_width.max(1) as f32;
+        let canvas = surface.canvas();
+        canvas.reset_matrix();
+        canvas.scale((scale_factor, scale_factor));
+        draw(canvas);
+        surface.flush();
+
+        window.gl_swap_window();
+        Ok(())
+    }
+
+    fn create_surface(&mut self, (width, height): (u32, u32)) -> Result<Surface, String> {
+        let framebuffer_info = FramebufferInfo {
+            fboid: 0,
+            format: FRAMEBUFFER_FORMAT,
+        };
+        let backend_render_target =
+            BackendRenderTarget::new_gl((width as i32, height as i32), 0, 8, framebuffer_info);
+        Surface::from_backend_render_target(
+            &mut self.context,
+            &backend_render_target,
+            SurfaceOrigin::BottomLeft,
+            ColorType::RGBA8888,
+            None,
+            None,
+        )
+        .ok_or_else(|| String::from("Could not create a Skia surface for the window"))
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use log::{error, info, warn};
+
+use super::CapturedFrame;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordingTarget {
+    // Numbered PNGs in this directory, which is created if needed
+    Images(String),
+    // A shell command that reads raw RGBA frames on stdin, such as
+    // ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -i - demo.gif
+    // {width} and {height} are replaced with the size of the first frame.
+    Encoder(String),
+}
+
+// Saves every Nth presented frame until dropped. Frames are only presented when something changes,
+// so an idle window records nothing. Encoding happens on a thread of its own so recording doesn't
+// slow the frames it is recording.
+pub struct FrameRecorder {
+    every: u64,
+    frames_seen: u64,
+    sender: Sender<CapturedFrame>,
+}
+
+impl FrameRecorder {
+    pub fn start(target: RecordingTarget, every: u64) -> FrameRecorder {
+        let (sender, receiver) = channel::<CapturedFrame>();
+        thread::spawn(move || {
+            let mut writer = FrameWriter::new(target);
+            for frame in receiver {
+                if let Err(error) = writer.write(frame) {
+                    error!("Recording stopped: {}", error);
+                    break;
+                }
+            }
+            // Also after a failed write, so the encoder isn't left waiting for more frames
+            writer.finish();
+        });
+
+        FrameRecorder {
+            every: every.max(1),
+            frames_seen: 0,
+            sender,
+        }
+    }
+
+    // Called once per drawn frame, before it is drawn, so the frame is only read back if needed
+    pub fn wants_frame(&mut self) -> bool {
+        self.frames_seen += 1;
+        (self.frames_seen - 1) % self.every == 0
+    }
+
+    pub fn record(&self, frame: CapturedFrame) {
+        self.sender.send(frame).ok();
+    }
+}
+
+struct FrameWriter {
+    target: RecordingTarget,
+    frames_written: u64,
+    size: Option<(u32, u32)>,
+    encoder: Option<Child>,
+}
+
+impl FrameWriter {
+    fn new(target: RecordingTarget) -> FrameWriter {
+        FrameWriter {
+            target,
+            frames_written: 0,
+            size: None,
+            encoder: None,
+        }
+    }
+
+    fn write(&mut self, frame: CapturedFrame) -> Result<(), String> {
+        // Neither a raw stream nor most image sequence tools cope with the size changing midway
+        let size = (frame.width, frame.height);
+        if *self.size.get_or_insert(size) != size {
+            warn!("Skipping a recorded frame because the window was resized");
+            return Ok(());
+        }
+
+        match &self.target {
+            RecordingTarget::Images(directory) => {
+                if self.frames_written == 0 {
+                    std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+                }
+                let mut path = PathBuf::from(directory);
+                path.push(format!("frame_{:06}.png", self.frames_written));
+                frame.save_png(&path.to_string_lossy())?;
+            }
+            RecordingTarget::Encoder(command) => {
+                if self.encoder.is_none() {
+                    let command = command
+                        .replace("{width}", &frame.width.to_string())
+                        .replace("{height}", &frame.height.to_string());
+                    self.encoder = Some(spawn_encoder(&command)?);
+                }
+                let stdin = self.encoder.as_mut().unwrap().stdin.as_mut().unwrap();
+                stdin
+                    .write_all(&frame.pixels)
+                    .map_err(|error| format!("Could not write to the encoder: {}", error))?;
+            }
+        }
+
+        self.frames_written += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) {
+        // Closing stdin tells the encoder there are no more frames
+        if let Some(mut encoder) = self.encoder.take() {
+            drop(encoder.stdin.take());
+            if let Err(error) = encoder.wait() {
+                error!("Encoder did not exit cleanly: {}", error);
+            }
+        }
+        info!("Recorded {} frames", self.frames_written);
+    }
+}
+
+fn spawn_encoder(command: &str) -> Result<Child, String> {
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Could not start the encoder {}: {}", command, error))
+}
@@ -1,29 +1,82 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::fmt;
+use std::rc::Rc;
 use std::sync::atomic::Ordering;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use skulpin::sdl2;
-use skulpin::sdl2::event::{Event, WindowEvent};
-use skulpin::sdl2::keyboard::Keycode;
-use skulpin::sdl2::video::FullscreenType;
-use skulpin::sdl2::Sdl;
+use skulpin::sdl2::controller::{Axis, Button, GameController};
+use skulpin::sdl2::event::{DisplayEvent, Event, WindowEvent};
+use skulpin::sdl2::keyboard::{Keycode, Scancode};
+use skulpin::sdl2::mouse::{Cursor, MouseButton, SystemCursor};
+use skulpin::sdl2::pixels::PixelFormatEnum;
+use skulpin::sdl2::rect::Rect;
+use skulpin::sdl2::surface::Surface;
+use skulpin::sdl2::sys::{SDL_Event, SDL_HitTestResult, SDL_Point, SDL_PushEvent, SDL_Window};
+use skulpin::sdl2::video::{DisplayMode, FullscreenType};
+use skulpin::sdl2::{GameControllerSubsystem, Sdl};
+use skulpin::skia_safe::Canvas;
 use skulpin::{
     CoordinateSystem, LogicalSize, PhysicalSize, PresentMode, Renderer as SkulpinRenderer,
     RendererBuilder, Sdl2Window, Window,
 };
 
-use crate::bridge::{produce_neovim_keybinding_string, UiCommand, BRIDGE};
+use crate::bridge::{
+    forget_layout_text, learn_layout_text, produce_neovim_keybinding_string, resolve_physical_key,
+    DeadKeyState, KeyboardModifiers, LockKeys, UiCommand, BRIDGE,
+};
 use crate::editor::EDITOR;
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use crate::redraw_scheduler::{Damage, REDRAW_SCHEDULER};
 use crate::renderer::Renderer;
 use crate::settings::*;
+use crate::window::{
+    add_menu_item, capture_canvas, drain_injected_events, forget_window_geometry, install_menu_bar,
+    is_native_fullscreen, load_window_geometry, parse_present_mode, parse_rendering_backend,
+    save_window_geometry, set_app_id, set_background_blur, set_gl_attributes, set_title_bar_theme,
+    set_window_backdrop, set_x11_class, take_theme_changed, take_tray_events,
+    toggle_native_fullscreen, watch_theme_changes, CapturedFrame, EventInjector, FrameRecorder,
+    FullscreenMode, InjectedEvent, OpenGlRenderer, RenderingBackend, SoftwareRenderer,
+    TitleBarTheme, TrayEvent, TrayIcon, Urgency, WindowBackdrop, WindowCommand, WindowConfig,
+    WindowError, WindowGeometry, WINDOW_COMMANDS,
+};
 use crate::INITIAL_DIMENSIONS;
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
 struct Asset;
 
+fn load_icon(data: &[u8]) -> Result<Surface<'static>, String> {
+    let icon = image::load_from_memory(data)
+        .map_err(|error| error.to_string())?
+        .to_rgba();
+    let (width, height) = icon.dimensions();
+    rgba_surface(width, height, icon.as_raw())
+}
+
+fn rgba_surface(width: u32, height: u32, rgba: &[u8]) -> Result<Surface<'static>, String> {
+    // These formats are named by their packed pixel value, so the one laid out as RGBA in memory
+    // depends on endianness
+    let format = if cfg!(target_endian = "little") {
+        PixelFormatEnum::ABGR8888
+    } else {
+        PixelFormatEnum::RGBA8888
+    };
+    let mut surface = Surface::new(width, height, format)?;
+    let pitch = surface.pitch() as usize;
+    let row_length = width as usize * 4;
+    surface.with_lock_mut(|pixels| {
+        for (row, source) in rgba.chunks(row_length).enumerate() {
+            pixels[row * pitch..row * pitch + row_length].copy_from_slice(source);
+        }
+    });
+
+    Ok(surface)
+}
+
 #[cfg(target_os = "windows")]
 fn windows_fix_dpi() {
     use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
@@ -33,6 +86,113 @@ fn windows_fix_dpi() {
     }
 }
 
+#[cfg(target_os = "windows")]
+fn windows_set_timer_resolution(precise: bool) {
+    use winapi::um::timeapi::{timeBeginPeriod, timeEndPeriod};
+    unsafe {
+        if precise {
+            timeBeginPeriod(1);
+        } else {
+            timeEndPeriod(1);
+        }
+    }
+}
+
+// Sleeping can overshoot by up to the OS timer resolution, so when precise timing is enabled the
+// final stretch before the deadline is spun instead.
+const SPIN_DURATION: Duration = Duration::from_millis(1);
+
+// While nothing is queued or animating the loop blocks on events instead of polling at the refresh
+// rate. It still wakes up this often in case something changed without telling the scheduler.
+const IDLE_WAIT_LIMIT: Duration = Duration::from_secs(1);
+
+// Unlike the event subsystem, SDL_PushEvent can be called from any thread, which lets neovim's
+// updates interrupt an idle wait
+fn push_wake_event(event_type: u32) {
+    unsafe {
+        let mut event: SDL_Event = std::mem::zeroed();
+        event.type_ = event_type;
+        SDL_PushEvent(&mut event);
+    }
+}
+
+fn sleep_until(deadline: Instant, precise: bool) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+
+    let remaining = deadline - now;
+    if precise {
+        if remaining > SPIN_DURATION {
+            sleep(remaining - SPIN_DURATION);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    } else {
+        sleep(remaining);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FrameSkipPolicy {
+    None,
+    SkipToLatest,
+    FixedStep,
+}
+
+impl FromValue for FrameSkipPolicy {
+    fn from_value(&mut self, value: Value) {
+        match value.as_str() {
+            Some("none") => *self = FrameSkipPolicy::None,
+            Some("skip_to_latest") => *self = FrameSkipPolicy::SkipToLatest,
+            Some("fixed_step") => *self = FrameSkipPolicy::FixedStep,
+            _ => error!(
+                "frame_skip_policy setting expected one of none, skip_to_latest or fixed_step, but received: {}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<FrameSkipPolicy> for Value {
+    fn from(policy: FrameSkipPolicy) -> Self {
+        match policy {
+            FrameSkipPolicy::None => "none".into(),
+            FrameSkipPolicy::SkipToLatest => "skip_to_latest".into(),
+            FrameSkipPolicy::FixedStep => "fixed_step".into(),
+        }
+    }
+}
+
+// Fixed step catch up gives up past this many frames behind, so a long stall like a suspend
+// doesn't turn into a burst of frames
+const MAX_CATCH_UP_FRAMES: u32 = 5;
+
+// Returns when the next frame should start along with how many frames were skipped to get there.
+// None lets the frame rate drop by starting the next frame immediately, SkipToLatest drops the
+// missed frames to stay on the original schedule, and FixedStep runs the missed frames back to
+// back to catch up.
+fn next_frame_deadline(
+    policy: &FrameSkipPolicy,
+    deadline: Instant,
+    now: Instant,
+    frame_length: Duration,
+) -> (Instant, u32) {
+    if now <= deadline {
+        return (deadline, 0);
+    }
+
+    let missed = ((now - deadline).as_secs_f64() / frame_length.as_secs_f64()) as u32 + 1;
+    match policy {
+        FrameSkipPolicy::None => (now, 0),
+        FrameSkipPolicy::SkipToLatest => (deadline + frame_length * missed, missed),
+        FrameSkipPolicy::FixedStep if missed > MAX_CATCH_UP_FRAMES => (now, missed),
+        FrameSkipPolicy::FixedStep => (deadline, 0),
+    }
+}
+
 fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer) {
     if new_size.width > 0 && new_size.height > 0 {
         let new_width = ((new_size.width + 1) as f32 / renderer.font_width) as u32;
@@ -45,19 +205,418 @@ fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer) {
     }
 }
 
+fn neovim_mouse_button(button: MouseButton) -> Option<&'static str> {
+    match button {
+        MouseButton::Left => Some("left"),
+        MouseButton::Middle => Some("middle"),
+        MouseButton::Right => Some("right"),
+        _ => None,
+    }
+}
+
+// Held buttons in the order they were pressed. Drags are reported for the earliest button still
+// held, so overlapping presses and releases don't lose track of an ongoing drag.
+#[derive(Default)]
+struct PressedButtons(Vec<MouseButton>);
+
+impl PressedButtons {
+    fn press(&mut self, button: MouseButton) {
+        if !self.0.contains(&button) {
+            self.0.push(button);
+        }
+    }
+
+    fn release(&mut self, button: MouseButton) {
+        self.0.retain(|pressed| *pressed != button);
+    }
+
+    fn drag_button(&self) -> Option<MouseButton> {
+        self.0.first().copied()
+    }
+}
+
+fn controller_keybinding(button: Button) -> Option<&'static str> {
+    match button {
+        Button::DPadUp => Some("<Up>"),
+        Button::DPadDown => Some("<Down>"),
+        Button::DPadLeft => Some("<Left>"),
+        Button::DPadRight => Some("<Right>"),
+        Button::A => Some("<CR>"),
+        Button::B => Some("<Esc>"),
+        _ => None,
+    }
+}
+
+// Stick positions this close to the center are reported as centered, since sticks rarely rest at
+// exactly zero
+const CONTROLLER_AXIS_DEADZONE: i16 = 8000;
+// Smallest change in an axis, from -1 to 1, that is reported again
+const CONTROLLER_AXIS_STEP: f32 = 0.1;
+
+fn normalize_axis(value: i16) -> f32 {
+    if value.abs() < CONTROLLER_AXIS_DEADZONE {
+        0.0
+    } else {
+        (value as f32 / i16::MAX as f32).max(-1.0)
+    }
+}
+
+fn system_cursor(name: &str) -> Option<SystemCursor> {
+    match name {
+        "arrow" => Some(SystemCursor::Arrow),
+        "ibeam" => Some(SystemCursor::IBeam),
+        "hand" => Some(SystemCursor::Hand),
+        "crosshair" => Some(SystemCursor::Crosshair),
+        "wait" => Some(SystemCursor::Wait),
+        "no" => Some(SystemCursor::No),
+        "resize_horizontal" => Some(SystemCursor::SizeWE),
+        "resize_vertical" => Some(SystemCursor::SizeNS),
+        "resize_nwse" => Some(SystemCursor::SizeNWSE),
+        "resize_nesw" => Some(SystemCursor::SizeNESW),
+        "resize_all" => Some(SystemCursor::SizeAll),
+        _ => None,
+    }
+}
+
+// Draws on top of neovim's content at the end of every frame, so an application embedding neovide
+// can add its own overlays. Any closure taking the same arguments is a DrawHandler. The canvas is
+// in logical coordinates, and the damage says which parts of the window were asked to be redrawn
+// this frame.
+pub trait DrawHandler {
+    fn draw(&mut self, canvas: &mut Canvas, window_size: LogicalSize, damage: &Damage);
+}
+
+impl<F: FnMut(&mut Canvas, LogicalSize, &Damage)> DrawHandler for F {
+    fn draw(&mut self, canvas: &mut Canvas, window_size: LogicalSize, damage: &Damage) {
+        self(canvas, window_size, damage)
+    }
+}
+
+enum RenderBackend {
+    Vulkan(SkulpinRenderer),
+    OpenGl(OpenGlRenderer),
+    Software(SoftwareRenderer),
+}
+
+fn create_vulkan_renderer(
+    window: &sdl2::video::Window,
+    config: &WindowConfig,
+) -> Result<SkulpinRenderer, WindowError> {
+    RendererBuilder::new()
+        .prefer_integrated_gpu()
+        .use_vulkan_debug_layer(false)
+        .present_mode_priority(config.present_modes.clone())
+        .coordinate_system(CoordinateSystem::Logical)
+        .build(&Sdl2Window::new(window))
+        .map_err(|error| WindowError::Renderer(error.to_string()))
+}
+
+// Distance from the edge of a frameless window in which dragging resizes it
+const FRAMELESS_RESIZE_BORDER: i32 = 4;
+
+// Shared with SDL's hit test callback, which can run while SDL is inside the platform's own move
+// or resize loop, so it reads this directly instead of going through the settings
+struct HitTestData {
+    drag_regions: Vec<(u64, u64, u64, u64)>,
+    cell_size: (f32, f32),
+    resize_border: bool,
+}
+
+fn hit_test(
+    data: &HitTestData,
+    (x, y): (i32, i32),
+    (width, height): (i32, i32),
+) -> SDL_HitTestResult {
+    if data.resize_border {
+        let left = x < FRAMELESS_RESIZE_BORDER;
+        let right = x >= width - FRAMELESS_RESIZE_BORDER;
+        let top = y < FRAMELESS_RESIZE_BORDER;
+        let bottom = y >= height - FRAMELESS_RESIZE_BORDER;
+        match (left, right, top, bottom) {
+            (true, _, true, _) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPLEFT,
+            (_, true, true, _) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPRIGHT,
+            (true, _, _, true) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMLEFT,
+            (_, true, _, true) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMRIGHT,
+            (true, _, _, _) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_LEFT,
+            (_, true, _, _) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_RIGHT,
+            (_, _, true, _) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_TOP,
+            (_, _, _, true) => return SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOM,
+            _ => {}
+        }
+    }
+
+    let (cell_width, cell_height) = data.cell_size;
+    let column = (x as f32 / cell_width) as u64;
+    let row = (y as f32 / cell_height) as u64;
+    let draggable = data.drag_regions.iter().any(
+        |&(region_column, region_row, region_width, region_height)| {
+            (region_column..region_column + region_width).contains(&column)
+                && (region_row..region_row + region_height).contains(&row)
+        },
+    );
+
+    if draggable {
+        SDL_HitTestResult::SDL_HITTEST_DRAGGABLE
+    } else {
+        SDL_HitTestResult::SDL_HITTEST_NORMAL
+    }
+}
+
+unsafe extern "C" fn hit_test_callback(
+    window: *mut SDL_Window,
+    area: *const SDL_Point,
+    data: *mut c_void,
+) -> SDL_HitTestResult {
+    let (mut width, mut height) = (0, 0);
+    sdl2::sys::SDL_GetWindowSize(window, &mut width, &mut height);
+    let area = &*area;
+    hit_test(
+        &*(data as *const HitTestData),
+        (area.x, area.y),
+        (width, height),
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloseAction {
+    Quit,
+    Hide,
+    Callback,
+}
+
+impl FromValue for CloseAction {
+    fn from_value(&mut self, value: Value) {
+        match value.as_str() {
+            Some("quit") => *self = CloseAction::Quit,
+            Some("hide") => *self = CloseAction::Hide,
+            Some("callback") => *self = CloseAction::Callback,
+            _ => error!(
+                "close_action setting expected one of quit, hide or callback, but received: {}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<CloseAction> for Value {
+    fn from(action: CloseAction) -> Self {
+        match action {
+            CloseAction::Quit => "quit".into(),
+            CloseAction::Hide => "hide".into(),
+            CloseAction::Callback => "callback".into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+}
+
+// Accumulated amounts at which a gesture step is reported. Distances are normalized to the touch
+// device and rotations are in radians.
+const PINCH_STEP: f32 = 0.05;
+const ROTATION_STEP: f32 = 0.3;
+const SWIPE_DISTANCE: f32 = 0.15;
+
+// Turns SDL's multi finger gesture motion into discrete steps. The swipe origin is where the
+// current set of fingers started, and restarts whenever the number of fingers changes.
+#[derive(Default)]
+struct GestureTracker {
+    fingers: u16,
+    origin: (f32, f32),
+    pinch_distance: f32,
+    rotation: f32,
+}
+
+impl GestureTracker {
+    fn update(
+        &mut self,
+        rotation_delta: f32,
+        distance_delta: f32,
+        position: (f32, f32),
+        fingers: u16,
+    ) -> Vec<String> {
+        if fingers != self.fingers {
+            self.reset();
+            self.fingers = fingers;
+            self.origin = position;
+        }
+
+        let mut events = Vec::new();
+
+        self.pinch_distance += distance_delta;
+        if self.pinch_distance.abs() > PINCH_STEP {
+            events.push(String::from(if self.pinch_distance > 0.0 {
+                "NeovideZoomIn"
+            } else {
+                "NeovideZoomOut"
+            }));
+            self.pinch_distance = 0.0;
+        }
+
+        // SDL measures angles with y pointing down, so a positive rotation is clockwise on screen
+        self.rotation += rotation_delta;
+        if self.rotation.abs() > ROTATION_STEP {
+            events.push(String::from(if self.rotation > 0.0 {
+                "NeovideRotateClockwise"
+            } else {
+                "NeovideRotateCounterClockwise"
+            }));
+            self.rotation = 0.0;
+        }
+
+        let (x, y) = position;
+        let (origin_x, origin_y) = self.origin;
+        let (dx, dy) = (x - origin_x, y - origin_y);
+        if dx.abs().max(dy.abs()) > SWIPE_DISTANCE {
+            let direction = if dx.abs() > dy.abs() {
+                if dx > 0.0 {
+                    "Right"
+                } else {
+                    "Left"
+                }
+            } else if dy > 0.0 {
+                "Down"
+            } else {
+                "Up"
+            };
+            events.push(format!("NeovideSwipe{}{}", fingers, direction));
+            self.origin = position;
+        }
+
+        events
+    }
+
+    fn reset(&mut self) {
+        *self = GestureTracker::default();
+    }
+}
+
+// Repeats used when only one of the delay and interval is configured
+const DEFAULT_KEY_REPEAT_DELAY: Duration = Duration::from_millis(500);
+const DEFAULT_KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(33);
+
+struct KeyRepeat {
+    scancode: Scancode,
+    keybinding: String,
+    next_repeat: Instant,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GrabState {
+    pub pointer_grabbed: bool,
+    pub keyboard_grabbed: bool,
+    pub relative_mouse: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppLifecycleState {
+    WillEnterBackground,
+    DidEnterBackground,
+    WillEnterForeground,
+    DidEnterForeground,
+    Terminating,
+    LowMemory,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplayInfo {
+    pub index: i32,
+    pub name: String,
+    pub bounds: Rect,
+    pub usable_bounds: Rect,
+    pub scale_factor: f32,
+    pub refresh_rate: i32,
+}
+
+impl DisplayInfo {
+    fn to_value(&self) -> Value {
+        let rect_value = |rect: Rect| {
+            Value::Map(vec![
+                (Value::from("x"), Value::from(rect.x())),
+                (Value::from("y"), Value::from(rect.y())),
+                (Value::from("width"), Value::from(rect.width())),
+                (Value::from("height"), Value::from(rect.height())),
+            ])
+        };
+        Value::Map(vec![
+            (Value::from("index"), Value::from(self.index)),
+            (Value::from("name"), Value::from(self.name.as_str())),
+            (Value::from("bounds"), rect_value(self.bounds)),
+            (Value::from("usable_bounds"), rect_value(self.usable_bounds)),
+            (Value::from("scale_factor"), Value::from(self.scale_factor)),
+            (Value::from("refresh_rate"), Value::from(self.refresh_rate)),
+        ])
+    }
+}
+
+// SDL's SDL_WINDOWPOS_CENTERED_DISPLAY macro, which the bindings don't include
+fn centered_on_display(display: i32) -> i32 {
+    (0x2FFF_0000 | display as u32) as i32
+}
+
 struct WindowWrapper {
     context: Sdl,
     window: sdl2::video::Window,
-    skulpin_renderer: SkulpinRenderer,
+    render_backend: RenderBackend,
     renderer: Renderer,
-    mouse_down: bool,
+    pressed_buttons: PressedButtons,
+    mouse_pixel_position: (i32, i32),
+    drag_origin: (i32, i32),
+    dragging: bool,
+    custom_cursor: bool,
+    // SDL frees the cursor when it is dropped, so the current one is kept here
+    mouse_cursor: Option<Cursor>,
+    mouse_hidden_while_typing: bool,
+    key_repeat: Option<KeyRepeat>,
+    // Set while a frame is being drawn, so a redraw requested from inside it is skipped
+    drawing: bool,
+    dead_keys: DeadKeyState,
+    ime_cursor_position: (i32, i32, i32),
+    touch_input: bool,
+    touch_finger: Option<i64>,
+    gestures: GestureTracker,
     mouse_position: LogicalSize,
     title: String,
     previous_size: LogicalSize,
+    scale_factor: f64,
     transparency: f32,
     fullscreen: bool,
+    exclusive_fullscreen: bool,
+    always_on_top: bool,
+    background_blur: bool,
+    title_bar_theme: Option<TitleBarTheme>,
+    window_backdrop: Option<WindowBackdrop>,
+    tray_icon_enabled: bool,
+    tray_icon: Option<TrayIcon>,
+    // Boxed so its address stays the same for SDL's hit test callback
+    hit_test_data: Box<HitTestData>,
     cached_size: (u32, u32),
     cached_position: (i32, i32),
+    // Last size and position while neither maximized nor fullscreen
+    windowed_bounds: ((u32, u32), (i32, i32)),
+    grab_state: GrabState,
+    sdl_start: Instant,
+    // When the earliest input since the last drawn frame happened
+    pending_input_time: Option<Instant>,
+    frame_number: u64,
+    event_number: u64,
+    frame_stats: FrameStats,
+    draw_handler: Option<Box<dyn DrawHandler>>,
+    backgrounded: bool,
+    rendering_paused: bool,
+    hidden: bool,
+    minimized: bool,
+    last_draw: Instant,
+    game_controller_subsystem: Option<GameControllerSubsystem>,
+    game_controllers: HashMap<u32, GameController>,
+    controller_axes: HashMap<(u32, String), f32>,
+    // Paths, or None for the pixels themselves, waiting for the next drawn frame
+    pending_captures: Vec<Option<String>>,
+    recorder: Option<FrameRecorder>,
 }
 
 pub fn window_geometry() -> Result<(u64, u64), String> {
@@ -102,70 +661,560 @@ pub fn window_geometry_or_default() -> (u64, u64) {
     window_geometry().unwrap_or(INITIAL_DIMENSIONS)
 }
 
+fn command_line_config() -> WindowConfig {
+    let mut config = WindowConfig::new()
+        .transparent(std::env::args().any(|arg| arg == "--transparent"))
+        .always_on_top(std::env::args().any(|arg| arg == "--always-on-top"))
+        .frameless(std::env::args().any(|arg| arg == "--frameless"))
+        .maximized(std::env::args().any(|arg| arg == "--maximized"))
+        .resizable(!std::env::args().any(|arg| arg == "--no-resize"))
+        .headless(std::env::args().any(|arg| arg == "--headless"))
+        .wayland(
+            cfg!(target_os = "linux")
+                && std::env::var_os("WAYLAND_DISPLAY").is_some()
+                && !std::env::args().any(|arg| arg == "--x11"),
+        );
+    if let Some(title) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--title=").map(str::to_string))
+    {
+        config = config.title(&title);
+    }
+    if let Some(path) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--icon=").map(str::to_string))
+    {
+        match std::fs::read(&path) {
+            Ok(icon) => config = config.icon(icon),
+            Err(error) => warn!("Could not read window icon {}: {}", path, error),
+        }
+    }
+    let display = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--display=").map(str::to_string))
+        .and_then(|display| display.parse().ok());
+    if let Some(display) = display {
+        config = config.display(display);
+    }
+    if let Some(name) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--backend=").map(str::to_string))
+    {
+        match parse_rendering_backend(&name) {
+            Some(backend) => config = config.backend(backend),
+            None => warn!("Unknown rendering backend {}", name),
+        }
+    }
+    if let Some(app_id) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--app-id=").map(str::to_string))
+    {
+        config = config.app_id(&app_id);
+    }
+    // Given as instance,class like xprop shows it
+    if let Some(wm_class) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--x11-class=").map(str::to_string))
+    {
+        let mut names = wm_class.splitn(2, ',');
+        let instance = names.next().unwrap_or_default();
+        let class = names.next().unwrap_or(instance);
+        config = config.x11_class(instance, class);
+    }
+    if std::env::args().any(|arg| arg == "--vsync") {
+        config = config.present_modes(vec![PresentMode::Fifo]);
+    }
+    if let Some(names) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--present-mode=").map(str::to_string))
+    {
+        let present_modes = names
+            .split(',')
+            .filter_map(|name| {
+                let present_mode = parse_present_mode(name);
+                if present_mode.is_none() {
+                    warn!("Unknown present mode {}", name);
+                }
+                present_mode
+            })
+            .collect::<Vec<_>>();
+        if !present_modes.is_empty() {
+            config = config.present_modes(present_modes);
+        }
+    }
+    if std::env::args().any(|arg| arg.starts_with("--geometry=")) {
+        let (width, height) = window_geometry_or_default();
+        config = config.initial_size(width, height);
+    }
+
+    config
+}
+
 impl WindowWrapper {
-    pub fn new() -> WindowWrapper {
-        let context = sdl2::init().expect("Failed to initialize sdl2");
-        let video_subsystem = context
-            .video()
-            .expect("Failed to create sdl video subsystem");
+    pub fn new(config: &WindowConfig) -> Result<WindowWrapper, WindowError> {
+        let context = sdl2::init().map_err(WindowError::Sdl)?;
+        let sdl_start = context
+            .timer()
+            .map(|timer| Instant::now() - Duration::from_millis(timer.ticks() as u64))
+            .unwrap_or_else(|_| Instant::now());
+        if config.headless {
+            // Has to be set before the video subsystem starts
+            sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+        } else if config.wayland {
+            sdl2::hint::set("SDL_VIDEODRIVER", "wayland");
+        }
+        if let Some(app_id) = &config.app_id {
+            set_app_id(app_id);
+        }
+        let video_subsystem = match context.video() {
+            Err(error) if config.wayland && !config.headless => {
+                warn!(
+                    "Could not start the Wayland video driver, using X11: {}",
+                    error
+                );
+                sdl2::hint::set("SDL_VIDEODRIVER", "x11");
+                context.video()
+            }
+            video_subsystem => video_subsystem,
+        }
+        .map_err(WindowError::VideoSubsystem)?;
+        info!("Video driver: {}", video_subsystem.current_video_driver());
         video_subsystem.text_input().start();
 
-        let (width, height) = window_geometry_or_default();
+        let (width, height) = config.initial_size.unwrap_or(INITIAL_DIMENSIONS);
 
         let renderer = Renderer::new();
+        let max_surface_size = SETTINGS.get::<WindowSettings>().max_surface_size;
+        info!(
+            "Surface size limited to {}x{}",
+            max_surface_size, max_surface_size
+        );
         let logical_size = LogicalSize {
-            width: (width as f32 * renderer.font_width) as u32,
-            height: (height as f32 * renderer.font_height + 1.0) as u32,
+            width: ((width as f32 * renderer.font_width) as u32).min(max_surface_size),
+            height: ((height as f32 * renderer.font_height + 1.0) as u32).min(max_surface_size),
         };
 
         #[cfg(target_os = "windows")]
         windows_fix_dpi();
+        watch_theme_changes();
         sdl2::hint::set("SDL_MOUSE_FOCUS_CLICKTHROUGH", "1");
 
-        let sdl_window = video_subsystem
-            .window("Neovide", logical_size.width, logical_size.height)
-            .position_centered()
-            .allow_highdpi()
-            .resizable()
-            .vulkan()
-            .build()
-            .expect("Failed to create window");
-        info!("window created");
+        let transparency = SETTINGS.get::<WindowSettings>().transparency;
+        let transparent = transparency < 1.0 || config.transparent;
+        if transparent {
+            // Compositors only blend the window against the desktop if it doesn't bypass them
+            sdl2::hint::set("SDL_VIDEO_X11_NET_WM_BYPASS_COMPOSITOR", "0");
+        }
+
+        // SDL ties a window to one graphics API when it is created, so falling back to another
+        // backend means creating the window again
+        let build_window = |backend: RenderingBackend| {
+            let mut window_builder =
+                video_subsystem.window(&config.title, logical_size.width, logical_size.height);
+            window_builder.position_centered().allow_highdpi();
+            match backend {
+                RenderingBackend::Vulkan => {
+                    window_builder.vulkan();
+                }
+                RenderingBackend::OpenGl => {
+                    set_gl_attributes(&video_subsystem);
+                    window_builder.opengl();
+                }
+                RenderingBackend::Software => {}
+            }
+            if let Some(display) = config.display {
+                window_builder.position(centered_on_display(display), centered_on_display(display));
+            }
+            if config.resizable {
+                window_builder.resizable();
+            }
+            if config.maximized {
+                window_builder.maximized();
+            }
+            if config.frameless {
+                window_builder.borderless();
+            }
+            if config.x11_class.is_some() {
+                window_builder.hidden();
+            }
+            if config.always_on_top {
+                let flags = window_builder.window_flags()
+                    | sdl2::sys::SDL_WindowFlags::SDL_WINDOW_ALWAYS_ON_TOP as u32;
+                window_builder.set_window_flags(flags);
+            }
+            window_builder
+                .build()
+                .map_err(|error| WindowError::Window(error.to_string()))
+        };
+        let create_backend = |backend: RenderingBackend| -> Result<_, WindowError> {
+            let sdl_window = build_window(backend)?;
+            let render_backend = match backend {
+                RenderingBackend::Vulkan => {
+                    RenderBackend::Vulkan(create_vulkan_renderer(&sdl_window, config)?)
+                }
+                RenderingBackend::OpenGl => {
+                    let vsync = config.present_modes.first() == Some(&PresentMode::Fifo);
+                    OpenGlRenderer::new(&sdl_window, vsync)
+                        .map(RenderBackend::OpenGl)
+                        .map_err(WindowError::Renderer)?
+                }
+                RenderingBackend::Software => SoftwareRenderer::new(&sdl_window)
+                    .map(RenderBackend::Software)
+                    .map_err(WindowError::Renderer)?,
+            };
+            info!("Using the {:?} renderer", backend);
+            Ok((sdl_window, render_backend))
+        };
+
+        let backends = match config.backend {
+            // The dummy driver has no graphics API, only a window surface in memory
+            _ if config.headless => vec![RenderingBackend::Software],
+            Some(backend) => vec![backend],
+            None => vec![
+                RenderingBackend::Vulkan,
+                RenderingBackend::OpenGl,
+                RenderingBackend::Software,
+            ],
+        };
+        let mut created = Err(WindowError::Renderer(String::from(
+            "No rendering backend to try",
+        )));
+        for backend in backends {
+            created = create_backend(backend);
+            match &created {
+                Ok(_) => break,
+                Err(error) => warn!("Could not use the {:?} renderer: {}", backend, error),
+            }
+        }
+        let (mut sdl_window, render_backend) = created?;
 
-        let skulpin_renderer = {
-            let sdl_window_wrapper = Sdl2Window::new(&sdl_window);
-            RendererBuilder::new()
-                .prefer_integrated_gpu()
-                .use_vulkan_debug_layer(false)
-                .present_mode_priority(vec![PresentMode::Immediate])
-                .coordinate_system(CoordinateSystem::Logical)
-                .build(&sdl_window_wrapper)
-                .expect("Failed to create renderer")
+        if let Some((instance, class)) = &config.x11_class {
+            if let Err(error) = set_x11_class(&sdl_window, instance, class) {
+                warn!("Could not set WM_CLASS: {}", error);
+            }
+            sdl_window.show();
+        }
+
+        if config.always_on_top {
+            // The setting is the source of truth once running, so it starts out matching
+            let mut settings = SETTINGS.get::<WindowSettings>();
+            settings.always_on_top = true;
+            SETTINGS.set(&settings);
+        }
+
+        let icon = match &config.icon {
+            Some(icon) => load_icon(icon),
+            None => Asset::get("nvim.ico")
+                .ok_or_else(|| String::from("icon asset is missing"))
+                .and_then(|icon| load_icon(&icon)),
         };
+        match icon {
+            Ok(icon) => sdl_window.set_icon(icon),
+            Err(error) => warn!("Could not set window icon: {}", error),
+        }
+        info!("window created");
+
+        if !config.headless {
+            install_menu_bar();
+        }
+
+        if transparent {
+            if let Err(error) = sdl_window.set_opacity(transparency) {
+                warn!(
+                    "Window transparency is not supported on this platform: {}",
+                    error
+                );
+            }
+        }
 
         info!("renderer created");
 
-        WindowWrapper {
+        let scale_factor = Sdl2Window::new(&sdl_window).scale_factor();
+
+        let cell_size = (renderer.font_width, renderer.font_height);
+        let windowed_bounds = (sdl_window.size(), sdl_window.position());
+        let mut window_wrapper = WindowWrapper {
             context,
             window: sdl_window,
-            skulpin_renderer,
+            render_backend,
             renderer,
-            mouse_down: false,
+            pressed_buttons: PressedButtons::default(),
+            mouse_pixel_position: (0, 0),
+            drag_origin: (0, 0),
+            dragging: false,
+            custom_cursor: false,
+            mouse_cursor: None,
+            mouse_hidden_while_typing: false,
+            key_repeat: None,
+            drawing: false,
+            dead_keys: DeadKeyState::default(),
+            ime_cursor_position: (0, 0, 0),
+            touch_input: false,
+            touch_finger: None,
+            gestures: GestureTracker::default(),
             mouse_position: LogicalSize {
                 width: 0,
                 height: 0,
             },
-            title: String::from("Neovide"),
+            title: config.title.clone(),
             previous_size: logical_size,
-            transparency: 1.0,
+            scale_factor,
+            transparency,
             fullscreen: false,
+            exclusive_fullscreen: false,
+            always_on_top: config.always_on_top,
+            background_blur: false,
+            title_bar_theme: None,
+            window_backdrop: None,
+            tray_icon_enabled: false,
+            tray_icon: None,
+            hit_test_data: Box::new(HitTestData {
+                drag_regions: Vec::new(),
+                cell_size,
+                resize_border: config.frameless && config.resizable,
+            }),
             cached_size: (0, 0),
             cached_position: (0, 0),
+            windowed_bounds,
+            grab_state: GrabState::default(),
+            sdl_start,
+            pending_input_time: None,
+            frame_number: 0,
+            frame_stats: FrameStats::default(),
+            event_number: 0,
+            draw_handler: None,
+            backgrounded: false,
+            rendering_paused: false,
+            hidden: false,
+            minimized: false,
+            last_draw: Instant::now(),
+            game_controller_subsystem: None,
+            game_controllers: HashMap::new(),
+            controller_axes: HashMap::new(),
+            pending_captures: Vec::new(),
+            recorder: None,
+        };
+
+        let hit_test_data: *const HitTestData = &*window_wrapper.hit_test_data;
+        let hit_test_result = unsafe {
+            sdl2::sys::SDL_SetWindowHitTest(
+                window_wrapper.window.raw(),
+                Some(hit_test_callback),
+                hit_test_data as *mut c_void,
+            )
+        };
+        if hit_test_result != 0 {
+            warn!("Window drag regions are not supported on this platform");
         }
+
+        // An explicit initial size, such as from --geometry, takes precedence over the remembered
+        // geometry
+        if config.initial_size.is_none() && !config.maximized && config.display.is_none() {
+            if let Some(geometry) = load_window_geometry() {
+                window_wrapper.restore_geometry(geometry);
+            }
+        }
+
+        Ok(window_wrapper)
     }
 
-    pub fn toggle_fullscreen(&mut self) {
+    pub fn geometry(&self) -> WindowGeometry {
         if self.fullscreen {
+            let (size, position) = fullscreen_bounds(
+                (self.cached_size, self.cached_position),
+                self.windowed_bounds,
+            );
+            WindowGeometry {
+                size,
+                position,
+                maximized: false,
+                fullscreen: Some(FullscreenMode::Desktop {
+                    display: self.window.display_index().unwrap_or(0),
+                }),
+            }
+        } else if self.is_maximized() {
+            let (size, position) = self.windowed_bounds;
+            WindowGeometry {
+                size,
+                position,
+                maximized: true,
+                fullscreen: None,
+            }
+        } else {
+            WindowGeometry {
+                size: self.window.size(),
+                position: self.window.position(),
+                maximized: false,
+                fullscreen: None,
+            }
+        }
+    }
+
+    pub fn is_maximized(&self) -> bool {
+        self.window.window_flags() & sdl2::sys::SDL_WindowFlags::SDL_WINDOW_MAXIMIZED as u32 != 0
+    }
+
+    // Displays can be connected and disconnected while running, so the list is queried fresh each
+    // time rather than cached.
+    pub fn displays(&self) -> Vec<DisplayInfo> {
+        let video_subsystem = self.window.subsystem();
+        let display_count = video_subsystem.num_video_displays().unwrap_or(0);
+
+        (0..display_count)
+            .filter_map(|index| {
+                let bounds = video_subsystem.display_bounds(index).ok()?;
+                Some(DisplayInfo {
+                    index,
+                    name: video_subsystem.display_name(index).unwrap_or_default(),
+                    bounds,
+                    usable_bounds: video_subsystem
+                        .display_usable_bounds(index)
+                        .unwrap_or(bounds),
+                    scale_factor: video_subsystem
+                        .display_dpi(index)
+                        .map_or(1.0, |(ddpi, _, _)| ddpi / 96.0),
+                    refresh_rate: video_subsystem
+                        .current_display_mode(index)
+                        .map_or(0, |mode| mode.refresh_rate),
+                })
+            })
+            .collect()
+    }
+
+    pub fn restore_geometry(&mut self, geometry: WindowGeometry) {
+        let (width, height) = geometry.size;
+        let (x, y) = geometry.position;
+        self.window.set_size(width, height).ok();
+        self.window.set_position(
+            sdl2::video::WindowPos::Positioned(x),
+            sdl2::video::WindowPos::Positioned(y),
+        );
+        self.windowed_bounds = (geometry.size, geometry.position);
+
+        if geometry.maximized {
+            self.window.maximize();
+        }
+
+        if let Some(FullscreenMode::Desktop { display }) = geometry.fullscreen {
+            let displays = self.displays();
+            debug!("Connected displays: {:?}", displays);
+            if displays.iter().any(|info| info.index == display) {
+                // Fullscreen is entered on the next settings synchronization, which caches the
+                // windowed size and position restored above
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.fullscreen = true;
+                SETTINGS.set(&settings);
+            } else {
+                warn!(
+                    "Display {} is no longer connected, restoring window geometry without fullscreen",
+                    display
+                );
+            }
+        }
+    }
+
+    pub fn save_geometry(&self) {
+        if SETTINGS.get::<WindowSettings>().remember_geometry {
+            save_window_geometry(&self.geometry());
+        } else {
+            forget_window_geometry();
+        }
+    }
+
+    // SDL event timestamps are in milliseconds since SDL was initialized
+    pub fn event_time(&self, timestamp: u32) -> Instant {
+        self.sdl_start + Duration::from_millis(timestamp as u64)
+    }
+
+    // Injected events have no timestamp, so they don't count towards input latency
+    pub fn handle_input_time(&mut self, timestamp: u32) {
+        if timestamp != 0 {
+            let time = self.event_time(timestamp);
+            self.pending_input_time.get_or_insert(time);
+        }
+    }
+
+    pub fn grab_state(&self) -> GrabState {
+        self.grab_state
+    }
+
+    pub fn set_pointer_grab(&mut self, grabbed: bool) {
+        self.window.set_grab(grabbed);
+        self.grab_state.pointer_grabbed = self.window.grab();
+    }
+
+    pub fn set_keyboard_grab(&mut self, grabbed: bool) {
+        // SDL only grabs the keyboard alongside the pointer, and only when this hint is set at
+        // the moment the grab is applied, so the current pointer grab is reapplied here.
+        sdl2::hint::set("SDL_GRAB_KEYBOARD", if grabbed { "1" } else { "0" });
+        let pointer_grabbed = self.grab_state.pointer_grabbed;
+        self.window.set_grab(false);
+        self.window.set_grab(pointer_grabbed);
+        self.grab_state.keyboard_grabbed = grabbed;
+    }
+
+    pub fn set_relative_mouse(&mut self, relative: bool) {
+        let mouse = self.context.mouse();
+        mouse.set_relative_mouse_mode(relative);
+        self.grab_state.relative_mouse = mouse.relative_mouse_mode();
+    }
+
+    // Exclusive fullscreen keeps the display's current resolution, and switches to the requested
+    // refresh rate when the display offers it there
+    fn exclusive_display_mode(&self) -> Option<DisplayMode> {
+        let video_subsystem = self.window.subsystem();
+        let display = self.window.display_index().ok()?;
+        let desktop_mode = video_subsystem.desktop_display_mode(display).ok()?;
+        let refresh_rate = SETTINGS.get::<WindowSettings>().fullscreen_refresh_rate;
+        if refresh_rate <= 0 {
+            return Some(desktop_mode);
+        }
+
+        let mode_count = video_subsystem.num_display_modes(display).unwrap_or(0);
+        let mode = (0..mode_count)
+            .filter_map(|index| video_subsystem.display_mode(display, index).ok())
+            .find(|mode| {
+                mode.w == desktop_mode.w
+                    && mode.h == desktop_mode.h
+                    && mode.refresh_rate == refresh_rate
+            });
+        if mode.is_none() {
+            warn!(
+                "Display {} has no {}Hz mode at {}x{}, keeping {}Hz",
+                display, refresh_rate, desktop_mode.w, desktop_mode.h, desktop_mode.refresh_rate
+            );
+        }
+        Some(mode.unwrap_or(desktop_mode))
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        // macOS sizes and restores the window itself in a fullscreen Space. Exclusive fullscreen
+        // changes the display mode, which a Space can't do.
+        let settings = SETTINGS.get::<WindowSettings>();
+        let entering_exclusive = settings.exclusive_fullscreen && !self.fullscreen;
+        if cfg!(target_os = "macos")
+            && !settings.macos_simple_fullscreen
+            && !self.exclusive_fullscreen
+            && !entering_exclusive
+        {
+            // Kept for the geometry saved while in the Space
+            if !self.fullscreen {
+                self.cached_size = self.window.size();
+                self.cached_position = self.window.position();
+            }
+            match toggle_native_fullscreen(&self.window) {
+                Ok(()) => {
+                    self.fullscreen = !self.fullscreen;
+                    return;
+                }
+                Err(error) => warn!("Could not use native fullscreen: {}", error),
+            }
+        }
+
+        if self.fullscreen && self.exclusive_fullscreen {
+            self.window.set_fullscreen(FullscreenType::Off).ok();
+            self.exclusive_fullscreen = false;
+            self.window
+                .set_size(self.cached_size.0, self.cached_size.1)
+                .ok();
+            self.window.set_position(
+                sdl2::video::WindowPos::Positioned(self.cached_position.0),
+                sdl2::video::WindowPos::Positioned(self.cached_position.1),
+            );
+        } else if self.fullscreen {
             if cfg!(target_os = "windows") {
                 unsafe {
                     let raw_handle = self.window.raw();
@@ -187,7 +1236,25 @@ impl WindowWrapper {
             self.cached_size = self.window.size();
             self.cached_position = self.window.position();
 
-            if cfg!(target_os = "windows") {
+            let exclusive_mode = if SETTINGS.get::<WindowSettings>().exclusive_fullscreen {
+                self.exclusive_display_mode()
+            } else {
+                None
+            };
+
+            if let Some(mode) = exclusive_mode {
+                let result = self
+                    .window
+                    .set_display_mode(mode)
+                    .and_then(|_| self.window.set_fullscreen(FullscreenType::True));
+                match result {
+                    Ok(()) => self.exclusive_fullscreen = true,
+                    Err(error) => {
+                        warn!("Could not enter exclusive fullscreen: {}", error);
+                        self.window.set_fullscreen(FullscreenType::Desktop).ok();
+                    }
+                }
+            } else if cfg!(target_os = "windows") {
                 let video_subsystem = self.window.subsystem();
                 if let Ok(rect) = self
                     .window
@@ -216,8 +1283,192 @@ impl WindowWrapper {
         self.fullscreen = !self.fullscreen;
     }
 
+    // Some Wayland compositors don't let clients keep themselves above other windows and ignore
+    // this entirely.
+    pub fn set_always_on_top(&mut self, enabled: bool) {
+        unsafe {
+            let raw_handle = self.window.raw();
+            sdl2::sys::SDL_SetWindowAlwaysOnTop(
+                raw_handle,
+                if enabled {
+                    sdl2::sys::SDL_bool::SDL_TRUE
+                } else {
+                    sdl2::sys::SDL_bool::SDL_FALSE
+                },
+            );
+        }
+        self.always_on_top = enabled;
+    }
+
+    pub fn is_always_on_top(&self) -> bool {
+        self.always_on_top
+    }
+
+    pub fn has_decorations(&self) -> bool {
+        self.window.window_flags() & sdl2::sys::SDL_WindowFlags::SDL_WINDOW_BORDERLESS as u32 == 0
+    }
+
+    // The area inside the window changes size along with the decorations, which update_size
+    // notices on the next frame and passes on to neovim
+    pub fn set_decorations(&mut self, decorations: bool) {
+        self.window.set_bordered(decorations);
+        self.hit_test_data.resize_border = !decorations && self.window_resizable();
+        REDRAW_SCHEDULER.queue_next_frame();
+    }
+
+    fn window_resizable(&self) -> bool {
+        self.window.window_flags() & sdl2::sys::SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32 != 0
+    }
+
+    pub fn set_icon(&mut self, data: &[u8]) {
+        match load_icon(data) {
+            Ok(icon) => self.window.set_icon(icon),
+            Err(error) => warn!("Could not set window icon: {}", error),
+        }
+    }
+
+    pub fn handle_window_command(&mut self, command: WindowCommand) {
+        match command {
+            // Stays until neovim changes its own title
+            WindowCommand::SetTitle(title) => {
+                self.window.set_title(&title).ok();
+            }
+            WindowCommand::SetSize(width, height) => {
+                self.window.set_size(width, height).ok();
+            }
+            WindowCommand::SetIcon(path) => {
+                match std::fs::read(&path).map_err(|error| error.to_string()) {
+                    Ok(data) => self.set_icon(&data),
+                    Err(error) => warn!("Could not read window icon {}: {}", path, error),
+                }
+            }
+            // Fullscreen and transparency are applied through their settings so the two stay in
+            // agreement
+            WindowCommand::ToggleFullscreen => {
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.fullscreen = !settings.fullscreen;
+                SETTINGS.set(&settings);
+            }
+            WindowCommand::SetTransparency(transparency) => {
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.transparency = transparency;
+                SETTINGS.set(&settings);
+            }
+            WindowCommand::Rumble(strength, duration) => self.rumble(strength, duration),
+            WindowCommand::SetClipboard(text) => {
+                if let Err(error) = self
+                    .window
+                    .subsystem()
+                    .clipboard()
+                    .set_clipboard_text(&text)
+                {
+                    warn!("Could not set clipboard: {}", error);
+                }
+            }
+            WindowCommand::SetMouseCursor(name) => self.set_mouse_cursor(&name),
+            WindowCommand::StartRecording(target, every) => {
+                info!("Recording frames to {:?}", target);
+                // Replacing a recorder drops it, which finishes the previous recording
+                self.recorder = Some(FrameRecorder::start(target, every));
+                REDRAW_SCHEDULER.queue_next_frame();
+            }
+            WindowCommand::StopRecording => {
+                if self.recorder.take().is_some() {
+                    BRIDGE.queue_command(UiCommand::UserEvent(String::from(
+                        "NeovideRecordingStopped",
+                    )));
+                }
+            }
+            WindowCommand::CaptureFrame(path) => {
+                self.pending_captures.push(path);
+                REDRAW_SCHEDULER.queue_next_frame();
+            }
+            WindowCommand::AddMenuItem {
+                id,
+                title,
+                key_equivalent,
+            } => {
+                if let Err(error) = add_menu_item(&id, &title, key_equivalent.as_deref()) {
+                    warn!("Could not add menu item {}: {}", id, error);
+                }
+            }
+            WindowCommand::Notify {
+                title,
+                body,
+                urgency,
+            } => {
+                if let Err(error) = self.notify(&title, &body, urgency) {
+                    warn!("Could not show a notification: {}", error);
+                }
+            }
+            WindowCommand::MoveToDisplay(display) => self.move_to_display(display),
+            WindowCommand::GetFrameStats => self.report_frame_stats(),
+            WindowCommand::GetDisplays => {
+                let displays = self.displays().iter().map(DisplayInfo::to_value).collect();
+                BRIDGE.queue_command(UiCommand::Displays(Value::Array(displays)));
+            }
+            WindowCommand::Maximize => self.window.maximize(),
+            WindowCommand::Minimize => self.window.minimize(),
+            WindowCommand::Restore => self.window.restore(),
+            WindowCommand::SetDecorations(decorations) => self.set_decorations(decorations),
+            WindowCommand::ToggleDecorations => self.set_decorations(!self.has_decorations()),
+            WindowCommand::SetDragRegions(regions) => self.hit_test_data.drag_regions = regions,
+            WindowCommand::SetMouseCursorImage {
+                width,
+                height,
+                pixels,
+                hotspot: (hotspot_x, hotspot_y),
+            } => {
+                match rgba_surface(width, height, &pixels)
+                    .and_then(|surface| Cursor::from_surface(surface, hotspot_x, hotspot_y))
+                {
+                    Ok(cursor) => {
+                        cursor.set();
+                        self.mouse_cursor = Some(cursor);
+                    }
+                    Err(error) => warn!("Could not create mouse cursor image: {}", error),
+                }
+            }
+            WindowCommand::GetClipboard => {
+                match self.window.subsystem().clipboard().clipboard_text() {
+                    Ok(text) => BRIDGE.queue_command(UiCommand::Clipboard(text)),
+                    Err(error) => warn!("Could not read clipboard: {}", error),
+                }
+            }
+            WindowCommand::InjectKey(name) => {
+                if let Err(error) = EventInjector::new().inject_key(&name) {
+                    warn!("Could not inject key: {}", error);
+                }
+            }
+            WindowCommand::InjectText(text) => {
+                EventInjector::new().inject(InjectedEvent::TextInput(text))
+            }
+            WindowCommand::InjectClick { x, y, button } => {
+                if let Err(error) = EventInjector::new().inject_click(x, y, &button) {
+                    warn!("Could not inject click: {}", error);
+                }
+            }
+            WindowCommand::InjectScroll(x, y) => {
+                EventInjector::new().inject(InjectedEvent::MouseWheel { x, y })
+            }
+        }
+    }
+
     pub fn synchronize_settings(&mut self) {
-        let editor_title = { EDITOR.lock().title.clone() };
+        let (editor_title, (grid_x, grid_y)) = {
+            let editor = EDITOR.lock();
+            (editor.title.clone(), editor.cursor.position)
+        };
+
+        let ime_cursor_position = (
+            (grid_x as f32 * self.renderer.font_width) as i32,
+            (grid_y as f32 * self.renderer.font_height) as i32,
+            self.renderer.font_height as i32,
+        );
+        if self.ime_cursor_position != ime_cursor_position {
+            let (x, y, height) = ime_cursor_position;
+            self.set_ime_cursor_position(x, y, height);
+        }
 
         if self.title != editor_title {
             self.title = editor_title;
@@ -240,135 +1491,1072 @@ impl WindowWrapper {
         if self.fullscreen != fullscreen {
             self.toggle_fullscreen();
         }
+
+        let settings = SETTINGS.get::<WindowSettings>();
+        let grab_state = self.grab_state();
+
+        if grab_state.pointer_grabbed != settings.pointer_grab {
+            self.set_pointer_grab(settings.pointer_grab);
+        }
+
+        if grab_state.keyboard_grabbed != settings.keyboard_grab {
+            self.set_keyboard_grab(settings.keyboard_grab);
+        }
+
+        if grab_state.relative_mouse != settings.relative_mouse {
+            self.set_relative_mouse(settings.relative_mouse);
+        }
+
+        self.renderer.set_render_scale(settings.render_scale);
+
+        if self.is_always_on_top() != settings.always_on_top {
+            self.set_always_on_top(settings.always_on_top);
+        }
+
+        self.hit_test_data.cell_size = (self.renderer.font_width, self.renderer.font_height);
+
+        if self.background_blur != settings.background_blur {
+            // Not retried when the platform can't blur, until the setting changes again
+            self.background_blur = settings.background_blur;
+            if let Err(error) = set_background_blur(&self.window, settings.background_blur) {
+                warn!("Could not set background blur: {}", error);
+            }
+        }
+
+        if cfg!(target_os = "windows") {
+            let theme_changed = take_theme_changed();
+            if theme_changed || self.title_bar_theme != Some(settings.title_bar_theme) {
+                // Also not retried on failure, since older versions of Windows will keep failing
+                self.title_bar_theme = Some(settings.title_bar_theme);
+                let dark = settings.title_bar_theme.is_dark();
+                if let Err(error) = set_title_bar_theme(&self.window, dark) {
+                    warn!("Could not set the title bar theme: {}", error);
+                }
+                if theme_changed {
+                    BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideThemeChanged")));
+                }
+            }
+
+            if self.window_backdrop != Some(settings.window_backdrop) {
+                self.window_backdrop = Some(settings.window_backdrop);
+                if let Err(error) = set_window_backdrop(&self.window, settings.window_backdrop) {
+                    warn!("Could not set the window backdrop: {}", error);
+                }
+            }
+        }
+
+        let tray_icon_enabled = settings.tray_icon || settings.minimize_to_tray;
+        if self.tray_icon_enabled != tray_icon_enabled {
+            // Not retried where there is no tray, until the setting changes again
+            self.tray_icon_enabled = tray_icon_enabled;
+            self.tray_icon = None;
+            if tray_icon_enabled {
+                match TrayIcon::new(&self.window, &self.title) {
+                    Ok(tray_icon) => self.tray_icon = Some(tray_icon),
+                    Err(error) => warn!("Could not add a tray icon: {}", error),
+                }
+            }
+        }
+
+        if self.custom_cursor != settings.custom_cursor {
+            self.set_custom_cursor_mode(settings.custom_cursor);
+        }
+
+        if self.touch_input != settings.touch_input {
+            self.touch_input = settings.touch_input;
+            // SDL turns touches into mouse events unless they are handled directly
+            sdl2::hint::set(
+                "SDL_TOUCH_MOUSE_EVENTS",
+                if self.touch_input { "0" } else { "1" },
+            );
+        }
+
+        if self.game_controller_subsystem.is_some() != settings.game_controller {
+            self.set_game_controller_enabled(settings.game_controller);
+        }
+
+        if self.rendering_paused != settings.pause_rendering {
+            if settings.pause_rendering {
+                self.pause_rendering();
+            } else {
+                self.resume_rendering();
+            }
+        }
     }
 
+    // Hiding is meant to be paired with something outside the window, like a tray icon, which
+    // shows it again. The callback action leaves the decision to a NeovideCloseRequested autocmd.
     pub fn handle_quit(&mut self) {
-        BRIDGE.queue_command(UiCommand::Quit);
+        let settings = SETTINGS.get::<WindowSettings>();
+        match settings.close_action {
+            CloseAction::Quit if settings.confirm_quit => {
+                BRIDGE.queue_command(UiCommand::CloseRequested)
+            }
+            CloseAction::Quit => BRIDGE.queue_command(UiCommand::Quit),
+            CloseAction::Hide => self.hide(),
+            CloseAction::Callback => {
+                BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideCloseRequested")))
+            }
+        }
     }
 
-    pub fn handle_keyboard_input(&mut self, keycode: Option<Keycode>, text: Option<String>) {
-        let modifiers = self.context.keyboard().mod_state();
+    pub fn hide(&mut self) {
+        self.window.hide();
+        self.hidden = true;
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideHidden")));
+    }
+
+    // The green title bar button enters and leaves a fullscreen Space without going through the
+    // setting, so the setting is brought in line with it.
+    pub fn handle_native_fullscreen_change(&mut self) {
+        if !cfg!(target_os = "macos") || self.exclusive_fullscreen {
+            return;
+        }
+        let native_fullscreen = is_native_fullscreen(&self.window);
+        if native_fullscreen != self.fullscreen {
+            // The window already fills the Space, but the windowed bounds are only updated
+            // outside of fullscreen
+            if native_fullscreen {
+                let (size, position) = self.windowed_bounds;
+                self.cached_size = size;
+                self.cached_position = position;
+            }
+            self.fullscreen = native_fullscreen;
+            let mut settings = SETTINGS.get::<WindowSettings>();
+            settings.fullscreen = native_fullscreen;
+            SETTINGS.set(&settings);
+        }
+    }
+
+    // Reported however the state changed, from a command or the title bar. Nothing is drawn while
+    // minimized, and neovim configs can pause their own work on NeovideMinimized.
+    pub fn handle_maximized(&mut self) {
+        self.minimized = false;
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideMaximized")));
+    }
+
+    pub fn handle_minimized(&mut self) {
+        self.minimized = true;
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideMinimized")));
+        if self.tray_icon.is_some() && SETTINGS.get::<WindowSettings>().minimize_to_tray {
+            self.hide();
+        }
+    }
+
+    // Windows shows notifications from a notification area icon, so the tray icon is added for
+    // the first one if it isn't there already, and stays until the tray_icon setting changes
+    #[cfg(target_os = "windows")]
+    fn notify(&mut self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        if self.tray_icon.is_none() {
+            self.tray_icon = Some(TrayIcon::new(&self.window, &self.title)?);
+        }
+        self.tray_icon
+            .as_ref()
+            .unwrap()
+            .show_balloon(title, body, urgency)
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn notify(&mut self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        crate::window::show_notification(title, body, urgency)
+    }
+
+    pub fn handle_tray_event(&mut self, event: TrayEvent) {
+        match event {
+            TrayEvent::Show => {
+                self.window.show();
+                self.window.restore();
+                self.window.raise();
+            }
+            // Goes through confirm_quit like closing the window, but is never turned into hiding
+            TrayEvent::Quit if SETTINGS.get::<WindowSettings>().confirm_quit => {
+                BRIDGE.queue_command(UiCommand::CloseRequested)
+            }
+            TrayEvent::Quit => BRIDGE.queue_command(UiCommand::Quit),
+        }
+    }
+
+    pub fn handle_restored(&mut self) {
+        self.minimized = false;
+        REDRAW_SCHEDULER.queue_next_frame();
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideRestored")));
+    }
+
+    pub fn handle_shown(&mut self) {
+        self.hidden = false;
+        // Present straight away so stale content from before the window was hidden isn't shown
+        self.redraw_now();
+    }
+
+    // The input method places its candidate list just below this rect, or above it when there
+    // isn't room below, so it covers the whole line the caret is on.
+    pub fn set_ime_cursor_position(&mut self, x: i32, y: i32, height: i32) {
+        self.ime_cursor_position = (x, y, height);
+        let width = self.renderer.font_width.max(1.0) as u32;
+        self.window
+            .subsystem()
+            .text_input()
+            .set_rect(Rect::new(x, y, width, height.max(1) as u32));
+    }
+
+    // Raw key events are sent alongside the keybindings for configs that need presses and releases
+    // of individual keys, which keybindings can't express
+    pub fn handle_raw_key(
+        &mut self,
+        pressed: bool,
+        keycode: Option<Keycode>,
+        scancode: Option<Scancode>,
+        repeat: bool,
+    ) {
+        if !SETTINGS.get::<WindowSettings>().raw_key_events {
+            return;
+        }
+
+        let KeyboardModifiers {
+            shift,
+            ctrl,
+            alt,
+            gui,
+            altgr,
+        } = self.modifiers();
+        let modifiers = [
+            (shift, "shift"),
+            (ctrl, "ctrl"),
+            (alt, "alt"),
+            (gui, "gui"),
+            (altgr, "altgr"),
+        ]
+        .iter()
+        .filter(|(held, _)| *held)
+        .map(|(_, name)| name.to_string())
+        .collect();
+
+        BRIDGE.queue_command(UiCommand::RawKey {
+            pressed,
+            keycode: keycode.map(|keycode| keycode.name()).unwrap_or_default(),
+            scancode: scancode
+                .map(|scancode| scancode.name().to_string())
+                .unwrap_or_default(),
+            modifiers,
+            repeat,
+        });
+    }
+
+    pub fn handle_ime_preedit(&mut self, text: String, start: i32, length: i32) {
+        BRIDGE.queue_command(UiCommand::ImePreedit {
+            text,
+            cursor: (start, length),
+        });
+    }
+
+    pub fn modifiers(&self) -> KeyboardModifiers {
+        self.context.keyboard().mod_state().into()
+    }
+
+    pub fn lock_keys(&self) -> LockKeys {
+        self.context.keyboard().mod_state().into()
+    }
+
+    pub fn handle_keyboard_input(
+        &mut self,
+        keycode: Option<Keycode>,
+        scancode: Option<Scancode>,
+        text: Option<String>,
+    ) {
+        let modifiers = self.modifiers();
 
         if keycode.is_some() || text.is_some() {
             trace!(
-                "Keyboard Input Received: keycode-{:?} modifiers-{:?} text-{:?}",
+                "[frame {}] Keyboard Input Received: keycode-{:?} scancode-{:?} modifiers-{:?} locks-{:?} text-{:?}",
+                self.frame_number,
                 keycode,
+                scancode,
                 modifiers,
+                self.lock_keys(),
                 text
             );
         }
 
-        if let Some(keybinding_string) = produce_neovim_keybinding_string(keycode, text, modifiers)
-        {
-            BRIDGE.queue_command(UiCommand::Keyboard(keybinding_string));
+        learn_layout_text(keycode, modifiers, text.as_deref());
+        let (keycode, text) = self.dead_keys.filter(keycode, text, modifiers);
+        let (keycode, text) = resolve_physical_key(keycode, scancode, text);
+
+        if let Some(keybinding_string) = produce_neovim_keybinding_string(keycode, text, modifiers)
+        {
+            if let (Some(scancode), Some((delay, _))) = (scancode, self.key_repeat_timing()) {
+                self.key_repeat = Some(KeyRepeat {
+                    scancode,
+                    keybinding: keybinding_string.clone(),
+                    next_repeat: Instant::now() + delay,
+                });
+            }
+            BRIDGE.queue_command(UiCommand::Keyboard(keybinding_string));
+            self.hide_mouse_while_typing();
+        }
+    }
+
+    // Held keys are repeated from the loop, so it has to keep running while one is down
+    fn can_idle(&self) -> bool {
+        !SETTINGS.get::<WindowSettings>().no_idle && self.key_repeat.is_none()
+    }
+
+    // When any of the key repeat settings are changed, repeats from the OS are ignored and held
+    // keys are repeated here instead so they behave the same everywhere
+    pub fn handles_key_repeat(&self) -> bool {
+        let settings = SETTINGS.get::<WindowSettings>();
+        !settings.key_repeat || settings.key_repeat_delay > 0 || settings.key_repeat_interval > 0
+    }
+
+    // The delay and interval of repeats made here, or None when keys aren't repeated here
+    fn key_repeat_timing(&self) -> Option<(Duration, Duration)> {
+        let settings = SETTINGS.get::<WindowSettings>();
+        if !self.handles_key_repeat() || !settings.key_repeat {
+            return None;
+        }
+
+        let delay = match settings.key_repeat_delay {
+            0 => DEFAULT_KEY_REPEAT_DELAY,
+            delay => Duration::from_millis(delay),
+        };
+        let interval = match settings.key_repeat_interval {
+            0 => DEFAULT_KEY_REPEAT_INTERVAL,
+            interval => Duration::from_millis(interval),
+        };
+        Some((delay, interval))
+    }
+
+    pub fn handle_key_up(&mut self, scancode: Option<Scancode>) {
+        if let (Some(key_repeat), Some(scancode)) = (&self.key_repeat, scancode) {
+            if key_repeat.scancode == scancode {
+                self.key_repeat = None;
+            }
+        }
+    }
+
+    pub fn repeat_held_key(&mut self) {
+        let interval = match self.key_repeat_timing() {
+            Some((_, interval)) => interval,
+            None => {
+                self.key_repeat = None;
+                return;
+            }
+        };
+
+        if let Some(key_repeat) = &mut self.key_repeat {
+            // Repeats missed during a stalled frame are dropped rather than sent in a burst
+            let now = Instant::now();
+            if key_repeat.next_repeat <= now {
+                BRIDGE.queue_command(UiCommand::Keyboard(key_repeat.keybinding.clone()));
+                key_repeat.next_repeat = now + interval;
+            }
+        }
+    }
+
+    // The system cursor is hidden only while the pointer is over the window, so the window
+    // decorations and everything outside still behave normally.
+    pub fn set_custom_cursor_mode(&mut self, enabled: bool) {
+        self.custom_cursor = enabled;
+        self.context.mouse().show_cursor(!enabled);
+    }
+
+    pub fn set_mouse_cursor(&mut self, name: &str) {
+        let system_cursor = match system_cursor(name) {
+            Some(system_cursor) => system_cursor,
+            None => {
+                warn!("Unknown mouse cursor: {}", name);
+                return;
+            }
+        };
+
+        match Cursor::from_system(system_cursor) {
+            Ok(cursor) => {
+                cursor.set();
+                self.mouse_cursor = Some(cursor);
+            }
+            Err(error) => warn!("Could not create mouse cursor {}: {}", name, error),
+        }
+    }
+
+    // Shown again by the next mouse motion
+    fn hide_mouse_while_typing(&mut self) {
+        if !self.mouse_hidden_while_typing
+            && SETTINGS.get::<WindowSettings>().hide_mouse_when_typing
+        {
+            self.context.mouse().show_cursor(false);
+            self.mouse_hidden_while_typing = true;
+        }
+    }
+
+    pub fn handle_pointer_enter(&mut self) {
+        if self.custom_cursor {
+            self.context.mouse().show_cursor(false);
+        }
+    }
+
+    pub fn handle_pointer_leave(&mut self) {
+        self.context.mouse().show_cursor(true);
+    }
+
+    pub fn handle_pointer_motion(&mut self, x: i32, y: i32) {
+        // While the mouse is captured motion is reported outside of the window as well, which is
+        // kept to the nearest edge so a drag keeps extending towards it
+        let (width, height) = self.window.size();
+        let x = x.max(0).min(width as i32 - 1);
+        let y = y.max(0).min(height as i32 - 1);
+
+        if self.mouse_hidden_while_typing {
+            self.context.mouse().show_cursor(!self.custom_cursor);
+            self.mouse_hidden_while_typing = false;
+        }
+
+        let previous_position = self.mouse_position;
+        let physical_size = PhysicalSize::new(
+            (x as f32 / self.renderer.font_width) as u32,
+            (y as f32 / self.renderer.font_height) as u32,
+        );
+
+        let sdl_window_wrapper = Sdl2Window::new(&self.window);
+        self.mouse_position = physical_size.to_logical(sdl_window_wrapper.scale_factor());
+        self.mouse_pixel_position = (x, y);
+        if let Some(button) = self
+            .pressed_buttons
+            .drag_button()
+            .and_then(neovim_mouse_button)
+        {
+            // Small movements while a button is held are treated as jitter in the click rather
+            // than the start of a drag
+            if !self.dragging {
+                let (origin_x, origin_y) = self.drag_origin;
+                let distance = (((x - origin_x).pow(2) + (y - origin_y).pow(2)) as f32).sqrt();
+                self.dragging = distance > SETTINGS.get::<WindowSettings>().drag_threshold;
+            }
+
+            if self.dragging && previous_position != self.mouse_position {
+                BRIDGE.queue_command(UiCommand::Drag {
+                    button: button.to_string(),
+                    position: (self.mouse_position.width, self.mouse_position.height),
+                });
+            }
+        }
+    }
+
+    pub fn handle_pointer_down(&mut self, button: MouseButton) {
+        if let Some(button_name) = neovim_mouse_button(button) {
+            BRIDGE.queue_command(UiCommand::MouseButton {
+                button: button_name.to_string(),
+                action: String::from("press"),
+                position: (self.mouse_position.width, self.mouse_position.height),
+            });
+        }
+        if self.pressed_buttons.drag_button().is_none() {
+            self.drag_origin = self.mouse_pixel_position;
+            if SETTINGS.get::<WindowSettings>().capture_mouse {
+                self.context.mouse().capture(true);
+            }
+        }
+        self.pressed_buttons.press(button);
+    }
+
+    pub fn handle_pointer_up(&mut self, button: MouseButton) {
+        if let Some(button_name) = neovim_mouse_button(button) {
+            BRIDGE.queue_command(UiCommand::MouseButton {
+                button: button_name.to_string(),
+                action: String::from("release"),
+                position: (self.mouse_position.width, self.mouse_position.height),
+            });
+        }
+        self.pressed_buttons.release(button);
+        if self.pressed_buttons.drag_button().is_none() {
+            self.dragging = false;
+            self.context.mouse().capture(false);
+        }
+    }
+
+    // The first finger down acts as the left mouse button. Coordinates are normalized to the
+    // window by SDL.
+    // Pens arrive as touches as well. SDL 2 only passes on their pressure, not tilt or whether the
+    // eraser end is used.
+    pub fn handle_touch(
+        &mut self,
+        finger_id: i64,
+        x: f32,
+        y: f32,
+        pressure: f32,
+        phase: TouchPhase,
+    ) {
+        if !self.touch_input {
+            return;
+        }
+
+        // Every finger is reported when asked for, which can be a lot of events, while only the
+        // first one acts as the mouse
+        if SETTINGS.get::<WindowSettings>().touch_events {
+            let (width, height) = self.window.size();
+            let physical_size = PhysicalSize::new(
+                (x * width as f32 / self.renderer.font_width) as u32,
+                (y * height as f32 / self.renderer.font_height) as u32,
+            );
+            let position: LogicalSize =
+                physical_size.to_logical(Sdl2Window::new(&self.window).scale_factor());
+            BRIDGE.queue_command(UiCommand::Touch {
+                phase: format!("{:?}", phase),
+                finger: finger_id,
+                position: (position.width, position.height),
+                pressure,
+            });
+        }
+
+        if self.touch_finger.is_none() && phase == TouchPhase::Started {
+            self.touch_finger = Some(finger_id);
+        }
+
+        if self.touch_finger != Some(finger_id) {
+            return;
+        }
+
+        let (width, height) = self.window.size();
+        self.handle_pointer_motion((x * width as f32) as i32, (y * height as f32) as i32);
+
+        match phase {
+            TouchPhase::Started => self.handle_pointer_down(MouseButton::Left),
+            TouchPhase::Moved => {}
+            TouchPhase::Ended => {
+                self.handle_pointer_up(MouseButton::Left);
+                self.touch_finger = None;
+                self.gestures.reset();
+            }
+        }
+    }
+
+    pub fn handle_multi_gesture(
+        &mut self,
+        rotation_delta: f32,
+        distance_delta: f32,
+        x: f32,
+        y: f32,
+        fingers: u16,
+    ) {
+        if !self.touch_input {
+            return;
+        }
+
+        for event_name in self
+            .gestures
+            .update(rotation_delta, distance_delta, (x, y), fingers)
+        {
+            BRIDGE.queue_command(UiCommand::UserEvent(event_name));
+        }
+    }
+
+    // The controller subsystem is only started on request since it slows down startup. SDL
+    // reports every already connected controller as added once it starts.
+    pub fn set_game_controller_enabled(&mut self, enabled: bool) {
+        if enabled {
+            match self.context.game_controller() {
+                Ok(subsystem) => self.game_controller_subsystem = Some(subsystem),
+                Err(error) => error!("Could not start game controller subsystem: {}", error),
+            }
+        } else {
+            self.game_controllers.clear();
+            self.controller_axes.clear();
+            self.game_controller_subsystem = None;
+        }
+    }
+
+    fn queue_controller_event(
+        &self,
+        controller: u32,
+        event: &str,
+        input: Option<String>,
+        value: f32,
+    ) {
+        BRIDGE.queue_command(UiCommand::Controller {
+            controller,
+            event: event.to_string(),
+            input,
+            value,
+        });
+    }
+
+    pub fn handle_controller_added(&mut self, joystick_index: u32) {
+        if let Some(subsystem) = &self.game_controller_subsystem {
+            match subsystem.open(joystick_index) {
+                Ok(controller) => {
+                    info!(
+                        "Game controller {} connected: {}",
+                        controller.instance_id(),
+                        controller.name()
+                    );
+                    let controller_id = controller.instance_id();
+                    self.game_controllers.insert(controller_id, controller);
+                    self.queue_controller_event(controller_id, "Connected", None, 0.0);
+                }
+                Err(error) => error!("Could not open game controller: {}", error),
+            }
+        }
+    }
+
+    pub fn handle_controller_removed(&mut self, controller_id: u32) {
+        if self.game_controllers.remove(&controller_id).is_some() {
+            info!("Game controller {} disconnected", controller_id);
+            self.controller_axes
+                .retain(|(axis_controller, _), _| *axis_controller != controller_id);
+            self.queue_controller_event(controller_id, "Disconnected", None, 0.0);
+        }
+    }
+
+    pub fn handle_controller_button(&mut self, controller_id: u32, button: Button, pressed: bool) {
+        if !self.game_controllers.contains_key(&controller_id) {
+            return;
+        }
+
+        trace!(
+            "Game controller {} button {:?} pressed: {}",
+            controller_id,
+            button,
+            pressed
+        );
+        let (event, value) = if pressed {
+            ("ButtonDown", 1.0)
+        } else {
+            ("ButtonUp", 0.0)
+        };
+        self.queue_controller_event(controller_id, event, Some(button.string()), value);
+
+        if pressed {
+            if let Some(keybinding) = controller_keybinding(button) {
+                BRIDGE.queue_command(UiCommand::Keyboard(keybinding.to_string()));
+            }
+        }
+    }
+
+    // Sticks and triggers report every small movement, so only changes of at least
+    // CONTROLLER_AXIS_STEP or returns to the center are passed on
+    pub fn handle_controller_axis(&mut self, controller_id: u32, axis: Axis, value: i16) {
+        if !self.game_controllers.contains_key(&controller_id) {
+            return;
+        }
+
+        let value = normalize_axis(value);
+        let last_value = self
+            .controller_axes
+            .entry((controller_id, axis.string()))
+            .or_insert(0.0);
+        let returned_to_center = value == 0.0 && *last_value != 0.0;
+        if returned_to_center || (value - *last_value).abs() >= CONTROLLER_AXIS_STEP {
+            *last_value = value;
+            self.queue_controller_event(controller_id, "Axis", Some(axis.string()), value);
+        }
+    }
+
+    pub fn rumble(&mut self, strength: f32, duration: u32) {
+        let intensity = (strength.max(0.0).min(1.0) * u16::MAX as f32) as u16;
+        for (controller_id, controller) in self.game_controllers.iter_mut() {
+            if let Err(error) = controller.set_rumble(intensity, intensity, duration) {
+                warn!(
+                    "Could not rumble game controller {}: {}",
+                    controller_id, error
+                );
+            }
+        }
+    }
+
+    pub fn handle_mouse_wheel(&mut self, x: i32, y: i32) {
+        let vertical_input_type = match y {
+            _ if y > 0 => Some("up"),
+            _ if y < 0 => Some("down"),
+            _ => None,
+        };
+
+        if let Some(input_type) = vertical_input_type {
+            BRIDGE.queue_command(UiCommand::Scroll {
+                direction: input_type.to_string(),
+                position: (self.mouse_position.width, self.mouse_position.height),
+            });
+        }
+
+        let horizontal_input_type = match y {
+            _ if x > 0 => Some("right"),
+            _ if x < 0 => Some("left"),
+            _ => None,
+        };
+
+        if let Some(input_type) = horizontal_input_type {
+            BRIDGE.queue_command(UiCommand::Scroll {
+                direction: input_type.to_string(),
+                position: (self.mouse_position.width, self.mouse_position.height),
+            });
+        }
+    }
+
+    pub fn handle_focus_lost(&mut self) {
+        // The release of a held key goes to whichever window has focus
+        self.key_repeat = None;
+        BRIDGE.queue_command(UiCommand::FocusLost);
+    }
+
+    pub fn handle_focus_gained(&mut self) {
+        BRIDGE.queue_command(UiCommand::FocusGained);
+        REDRAW_SCHEDULER.queue_next_frame();
+    }
+
+    // While paused, events are still pumped so the window stays responsive, and the last drawn
+    // frame remains on screen.
+    pub fn pause_rendering(&mut self) {
+        self.rendering_paused = true;
+    }
+
+    pub fn resume_rendering(&mut self) {
+        self.rendering_paused = false;
+        REDRAW_SCHEDULER.queue_next_frame();
+    }
+
+    pub fn handle_display_connected(&mut self, index: i32) {
+        info!("Display {} connected", index);
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from(
+            "NeovideDisplayConnected",
+        )));
+    }
+
+    pub fn handle_display_disconnected(&mut self, index: i32) {
+        info!("Display {} disconnected", index);
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from(
+            "NeovideDisplayDisconnected",
+        )));
+        self.clamp_to_displays();
+    }
+
+    pub fn move_to_display(&mut self, display: i32) {
+        let bounds = match self.displays().iter().find(|info| info.index == display) {
+            Some(info) => info.usable_bounds,
+            None => {
+                warn!("There is no display {} to move the window to", display);
+                return;
+            }
+        };
+
+        // Fullscreen is left and entered again around the move, so it ends up covering the new
+        // display and still returns to the windowed size afterwards
+        let fullscreen = self.fullscreen;
+        if fullscreen {
+            self.toggle_fullscreen();
+        }
+
+        let (width, height) = self.window.size();
+        let width = width.min(bounds.width());
+        let height = height.min(bounds.height());
+        self.window.set_size(width, height).ok();
+        self.window.set_position(
+            sdl2::video::WindowPos::Positioned(bounds.x() + (bounds.width() - width) as i32 / 2),
+            sdl2::video::WindowPos::Positioned(bounds.y() + (bounds.height() - height) as i32 / 2),
+        );
+
+        if fullscreen {
+            self.toggle_fullscreen();
+        }
+    }
+
+    // A window left entirely on a removed display would be unreachable, so it is moved onto the
+    // first remaining one
+    fn clamp_to_displays(&mut self) {
+        let displays = self.displays();
+        let (x, y) = self.window.position();
+        let (width, height) = self.window.size();
+        let window_bounds = Rect::new(x, y, width, height);
+
+        if displays
+            .iter()
+            .any(|display| display.usable_bounds.has_intersection(window_bounds))
+        {
+            return;
+        }
+
+        if let Some(display) = displays.first() {
+            let bounds = display.usable_bounds;
+            warn!(
+                "Window is off screen, moving it onto display {}",
+                display.index
+            );
+            self.window.set_position(
+                sdl2::video::WindowPos::Positioned(bounds.x()),
+                sdl2::video::WindowPos::Positioned(bounds.y()),
+            );
+        }
+    }
+
+    // Dropping several files at once sends each of them between a begin and complete event, which
+    // lets configs treat them as a group, such as opening them in tabs
+    pub fn handle_drop_begin(&mut self) {
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideDropBegin")));
+    }
+
+    pub fn handle_drop_complete(&mut self) {
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideDropComplete")));
+    }
+
+    pub fn handle_keymap_changed(&mut self) {
+        // What keys type is learned again from the new layout as they are pressed. Neovim configs
+        // can react through the autocmd too.
+        forget_layout_text();
+        debug!("Keymap changed");
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideKeymapChanged")));
+    }
+
+    pub fn handle_app_lifecycle(&mut self, state: AppLifecycleState) {
+        debug!("App lifecycle changed: {:?}", state);
+        match state {
+            AppLifecycleState::WillEnterBackground | AppLifecycleState::DidEnterBackground => {
+                self.backgrounded = true;
+            }
+            AppLifecycleState::WillEnterForeground => {}
+            AppLifecycleState::DidEnterForeground => {
+                self.backgrounded = false;
+                REDRAW_SCHEDULER.queue_next_frame();
+            }
+            // The OS is shutting the app down, so this can't be vetoed like a close request.
+            AppLifecycleState::Terminating => BRIDGE.queue_command(UiCommand::Quit),
+            AppLifecycleState::LowMemory => self.renderer.release_caches(),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    pub fn handle_live_resize(&mut self) {
+        self.update_size();
+        self.redraw_now();
+    }
+
+    // Covers both resizes by the user and ones made from code, since either is only noticed here
+    fn update_size(&mut self) {
+        if !self.fullscreen && !self.is_maximized() {
+            self.windowed_bounds = (self.window.size(), self.window.position());
+        }
+
+        let sdl_window_wrapper = Sdl2Window::new(&self.window);
+        let new_size = sdl_window_wrapper.logical_size();
+        if self.previous_size != new_size {
+            debug!(
+                "Window resized to {:?} logical, {:?} physical",
+                new_size,
+                sdl_window_wrapper.physical_size()
+            );
+            handle_new_grid_size(new_size, &self.renderer);
+            self.previous_size = new_size;
+            BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideResized")));
+        }
+
+        // SDL doesn't report dpi changes, such as from moving the window to another monitor, so
+        // the scale is compared every frame. Drawing is in logical coordinates and follows along
+        // on its own, but everything has to be redrawn at the new resolution.
+        let scale_factor = sdl_window_wrapper.scale_factor();
+        if (self.scale_factor - scale_factor).abs() > std::f64::EPSILON {
+            info!("Scale factor changed to {}", scale_factor);
+            self.scale_factor = scale_factor;
+            self.renderer.invalidate_surface();
+            BRIDGE.queue_command(UiCommand::UserEvent(String::from(
+                "NeovideScaleFactorChanged",
+            )));
         }
     }
 
-    pub fn handle_pointer_motion(&mut self, x: i32, y: i32) {
-        let previous_position = self.mouse_position;
-        let physical_size = PhysicalSize::new(
-            (x as f32 / self.renderer.font_width) as u32,
-            (y as f32 / self.renderer.font_height) as u32,
+    // Swapchain images larger than the device supports make drawing fail or crash the driver, which
+    // huge windows spanning many monitors or buggy window managers can otherwise trigger
+    fn clamp_surface_size(&mut self) {
+        let max_surface_size = SETTINGS.get::<WindowSettings>().max_surface_size;
+        let (drawable_width, drawable_height) = self.window.drawable_size();
+        if drawable_width <= max_surface_size && drawable_height <= max_surface_size {
+            return;
+        }
+
+        // The drawable size is in physical pixels while the window is sized in logical ones
+        let (width, height) = self.window.size();
+        let clamp = |size: u32, drawable_size: u32| {
+            if drawable_size > max_surface_size {
+                (size as u64 * max_surface_size as u64 / drawable_size as u64) as u32
+            } else {
+                size
+            }
+        };
+        let clamped_size = (clamp(width, drawable_width), clamp(height, drawable_height));
+
+        warn!(
+            "Window size {}x{} exceeds the surface limit, clamping to {}x{}",
+            width, height, clamped_size.0, clamped_size.1
         );
+        self.window.set_size(clamped_size.0, clamped_size.1).ok();
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideSizeClamped")));
+    }
 
-        let sdl_window_wrapper = Sdl2Window::new(&self.window);
-        self.mouse_position = physical_size.to_logical(sdl_window_wrapper.scale_factor());
-        if self.mouse_down && previous_position != self.mouse_position {
-            BRIDGE.queue_command(UiCommand::Drag(
-                self.mouse_position.width,
-                self.mouse_position.height,
-            ));
+    pub fn draw_frame(&mut self) -> bool {
+        if !BRIDGE.running.load(Ordering::Relaxed) {
+            return false;
         }
-    }
 
-    pub fn handle_pointer_down(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
-            action: String::from("press"),
-            position: (self.mouse_position.width, self.mouse_position.height),
-        });
-        self.mouse_down = true;
-    }
+        self.clamp_surface_size();
+        self.update_size();
 
-    pub fn handle_pointer_up(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
-            action: String::from("release"),
-            position: (self.mouse_position.width, self.mouse_position.height),
-        });
-        self.mouse_down = false;
-    }
+        debug!("[frame {}] Render Triggered", self.frame_number);
 
-    pub fn handle_mouse_wheel(&mut self, x: i32, y: i32) {
-        let vertical_input_type = match y {
-            _ if y > 0 => Some("up"),
-            _ if y < 0 => Some("down"),
-            _ => None,
+        if self.backgrounded || self.rendering_paused || self.hidden || self.minimized {
+            return true;
+        }
+
+        // Events are polled at the refresh rate, but drawing can be capped lower to save power
+        // while keeping input responsive
+        let settings = SETTINGS.get::<WindowSettings>();
+        let draw_capped =
+            settings.max_draw_rate > 0 && settings.max_draw_rate < settings.refresh_rate;
+        let dt = if draw_capped {
+            1.0 / settings.max_draw_rate as f32
+        } else {
+            1.0 / settings.refresh_rate as f32
         };
+        if draw_capped && self.last_draw.elapsed() < Duration::from_secs_f32(dt) {
+            return true;
+        }
 
-        if let Some(input_type) = vertical_input_type {
-            BRIDGE.queue_command(UiCommand::Scroll {
-                direction: input_type.to_string(),
-                position: (self.mouse_position.width, self.mouse_position.height),
-            });
+        if REDRAW_SCHEDULER.should_draw() || settings.no_idle {
+            return self.render(dt);
         }
 
-        let horizontal_input_type = match y {
-            _ if x > 0 => Some("right"),
-            _ if x < 0 => Some("left"),
-            _ => None,
-        };
+        true
+    }
 
-        if let Some(input_type) = horizontal_input_type {
-            BRIDGE.queue_command(UiCommand::Scroll {
-                direction: input_type.to_string(),
-                position: (self.mouse_position.width, self.mouse_position.height),
-            });
+    // Draws and presents immediately, skipping the frame limiter and the redraw scheduler. Called
+    // while a frame is already being drawn it does nothing and returns false.
+    pub fn redraw_now(&mut self) -> bool {
+        if self.drawing {
+            trace!("[frame {}] Skipped a nested redraw", self.frame_number);
+            return false;
         }
+        let dt = 1.0 / SETTINGS.get::<WindowSettings>().refresh_rate as f32;
+        self.render(dt)
     }
 
-    pub fn handle_focus_lost(&mut self) {
-        BRIDGE.queue_command(UiCommand::FocusLost);
+    // Encoding and writing the PNGs happens off the loop's thread so the next frames aren't held up
+    fn deliver_captures(&mut self, captured_frame: Option<CapturedFrame>) {
+        let paths = std::mem::take(&mut self.pending_captures);
+        let captured_frame = match captured_frame {
+            Some(captured_frame) => captured_frame,
+            None => {
+                error!("Could not read back the drawn frame");
+                return;
+            }
+        };
+
+        std::thread::spawn(move || {
+            for path in paths {
+                if let Some(path) = &path {
+                    if let Err(error) = captured_frame.save_png(path) {
+                        error!("Could not save the frame to {}: {}", path, error);
+                        continue;
+                    }
+                }
+                BRIDGE.queue_command(UiCommand::FrameCaptured(
+                    captured_frame.to_value(path.as_deref()),
+                ));
+            }
+        });
     }
 
-    pub fn handle_focus_gained(&mut self) {
-        BRIDGE.queue_command(UiCommand::FocusGained);
-        REDRAW_SCHEDULER.queue_next_frame();
+    fn report_frame_stats(&mut self) {
+        let frame_stats = std::mem::take(&mut self.frame_stats);
+        BRIDGE.queue_command(UiCommand::FrameStats(frame_stats.to_value()));
     }
 
-    pub fn draw_frame(&mut self) -> bool {
-        if !BRIDGE.running.load(Ordering::Relaxed) {
+    fn render(&mut self, dt: f32) -> bool {
+        self.last_draw = Instant::now();
+        REDRAW_SCHEDULER.run_animations(dt);
+        let damage = REDRAW_SCHEDULER.take_damage();
+
+        let sdl_window_wrapper = Sdl2Window::new(&self.window);
+        let current_size = self.previous_size;
+        let renderer = &mut self.renderer;
+        let draw_handler = &mut self.draw_handler;
+        let mut font_changed = false;
+        let mut draw_time = Duration::default();
+        let window_size = sdl_window_wrapper.logical_size();
+        let record = self
+            .recorder
+            .as_mut()
+            .map_or(false, FrameRecorder::wants_frame);
+        let capture = record || !self.pending_captures.is_empty();
+        let mut captured_frame = None;
+        let mut draw = |canvas: &mut Canvas| {
+            let _draw_span = profile_span!("draw");
+            let draw_start = Instant::now();
+            font_changed = renderer.draw(canvas, window_size, dt);
+            if let Some(draw_handler) = draw_handler {
+                draw_handler.draw(canvas, window_size, &damage);
+            }
+            draw_time = draw_start.elapsed();
+            if capture {
+                captured_frame = capture_canvas(canvas);
+            }
+        };
+        // Covers acquiring the swapchain image and presenting as well as the draw span inside it
+        let _render_span = profile_span!("render");
+        self.drawing = true;
+        let result = match &mut self.render_backend {
+            RenderBackend::Vulkan(skulpin_renderer) => skulpin_renderer
+                .draw(&sdl_window_wrapper, |canvas, _| draw(canvas))
+                .map_err(|error| format!("{:?}", error)),
+            RenderBackend::OpenGl(opengl_renderer) => opengl_renderer.draw(&self.window, draw),
+            RenderBackend::Software(software_renderer) => {
+                software_renderer.draw(&self.window, draw)
+            }
+        };
+        self.drawing = false;
+        if let Err(error) = result {
+            error!("Render failed. Closing: {}", error);
             return false;
         }
 
-        let sdl_window_wrapper = Sdl2Window::new(&self.window);
-        let new_size = sdl_window_wrapper.logical_size();
-        if self.previous_size != new_size {
-            handle_new_grid_size(new_size, &self.renderer);
-            self.previous_size = new_size;
+        if record {
+            if let (Some(recorder), Some(frame)) = (&self.recorder, &captured_frame) {
+                recorder.record(frame.clone());
+            }
+        }
+        if !self.pending_captures.is_empty() {
+            self.deliver_captures(captured_frame);
         }
 
-        debug!("Render Triggered");
+        self.frame_stats.frames_drawn += 1;
+        self.frame_stats.draw_time += draw_time;
+        self.frame_stats.present_time += self.last_draw.elapsed() - draw_time;
+        if let Some(input_time) = self.pending_input_time.take() {
+            self.frame_stats.input_frames += 1;
+            self.frame_stats.input_latency += Instant::now().saturating_duration_since(input_time);
+        }
 
-        let current_size = self.previous_size;
+        if font_changed {
+            self.handle_font_changed(current_size);
+        }
 
-        if REDRAW_SCHEDULER.should_draw() || SETTINGS.get::<WindowSettings>().no_idle {
-            let renderer = &mut self.renderer;
-            let error = self
-                .skulpin_renderer
-                .draw(&sdl_window_wrapper, |canvas, coordinate_system_helper| {
-                    let dt = 1.0 / (SETTINGS.get::<WindowSettings>().refresh_rate as f32);
+        true
+    }
 
-                    if renderer.draw(canvas, &coordinate_system_helper, dt) {
-                        handle_new_grid_size(current_size, &renderer)
-                    }
-                })
-                .is_err();
-            if error {
-                error!("Render failed. Closing");
-                return false;
-            }
+    // Either the grid follows the new font size and the window stays put, or the window is
+    // resized around the grid. Maximized and fullscreen windows always keep their size.
+    fn handle_font_changed(&mut self, current_size: LogicalSize) {
+        if SETTINGS
+            .get::<WindowSettings>()
+            .keep_grid_size_on_font_change
+            && !self.is_maximized()
+            && !self.fullscreen
+        {
+            let (grid_width, grid_height) = {
+                let editor = EDITOR.lock();
+                (editor.grid.width, editor.grid.height)
+            };
+            let width = (grid_width as f32 * self.renderer.font_width).ceil() as u32;
+            let height = (grid_height as f32 * self.renderer.font_height).ceil() as u32;
+            // The grid size is sent again once the resize is noticed
+            self.window.set_size(width, height).ok();
+        } else {
+            handle_new_grid_size(current_size, &self.renderer);
         }
 
-        true
+        BRIDGE.queue_command(UiCommand::UserEvent(String::from("NeovideFontChanged")));
     }
 }
 
@@ -376,8 +2564,48 @@ impl WindowWrapper {
 struct WindowSettings {
     refresh_rate: u64,
     transparency: f32,
+    background_blur: bool,
+    // Windows only, like the backdrop. Auto follows the system's app theme as it changes.
+    title_bar_theme: TitleBarTheme,
+    window_backdrop: WindowBackdrop,
+    tray_icon: bool,
+    // Minimizing hides the window, leaving only the tray icon to show it again
+    minimize_to_tray: bool,
     no_idle: bool,
     fullscreen: bool,
+    exclusive_fullscreen: bool,
+    // Fullscreen on macOS covers the screen in place, as on other platforms, rather than moving to
+    // a Space of its own
+    macos_simple_fullscreen: bool,
+    fullscreen_refresh_rate: i32,
+    always_on_top: bool,
+    max_surface_size: u32,
+    live_resize: bool,
+    confirm_quit: bool,
+    pointer_grab: bool,
+    keyboard_grab: bool,
+    relative_mouse: bool,
+    precise_frame_timing: bool,
+    pause_rendering: bool,
+    render_scale: f32,
+    keep_grid_size_on_font_change: bool,
+    drag_threshold: f32,
+    capture_mouse: bool,
+    custom_cursor: bool,
+    hide_mouse_when_typing: bool,
+    raw_key_events: bool,
+    touch_events: bool,
+    key_repeat: bool,
+    key_repeat_delay: u64,
+    key_repeat_interval: u64,
+    touch_input: bool,
+    max_draw_rate: u64,
+    frame_skip_policy: FrameSkipPolicy,
+    redraw_on_window_events: bool,
+    game_controller: bool,
+    remember_geometry: bool,
+    close_action: CloseAction,
+    frame_stats_interval: u64,
 }
 
 pub fn initialize_settings() {
@@ -388,67 +2616,546 @@ pub fn initialize_settings() {
     SETTINGS.set(&WindowSettings {
         refresh_rate: 60,
         transparency: 1.0,
+        background_blur: false,
+        title_bar_theme: TitleBarTheme::Auto,
+        window_backdrop: WindowBackdrop::None,
+        tray_icon: false,
+        minimize_to_tray: false,
         no_idle,
         fullscreen: false,
+        exclusive_fullscreen: false,
+        macos_simple_fullscreen: false,
+        fullscreen_refresh_rate: 0,
+        always_on_top: false,
+        max_surface_size: 16384,
+        live_resize: true,
+        confirm_quit: false,
+        pointer_grab: false,
+        keyboard_grab: false,
+        relative_mouse: false,
+        precise_frame_timing: false,
+        pause_rendering: false,
+        render_scale: 1.0,
+        keep_grid_size_on_font_change: false,
+        drag_threshold: 3.0,
+        capture_mouse: true,
+        custom_cursor: false,
+        hide_mouse_when_typing: false,
+        raw_key_events: false,
+        touch_events: false,
+        key_repeat: true,
+        key_repeat_delay: 0,
+        key_repeat_interval: 0,
+        touch_input: false,
+        max_draw_rate: 0,
+        frame_skip_policy: FrameSkipPolicy::None,
+        redraw_on_window_events: false,
+        game_controller: false,
+        remember_geometry: false,
+        close_action: CloseAction::Quit,
+        frame_stats_interval: 0,
     });
 
     register_nvim_setting!("refresh_rate", WindowSettings::refresh_rate);
     register_nvim_setting!("transparency", WindowSettings::transparency);
     register_nvim_setting!("no_idle", WindowSettings::no_idle);
     register_nvim_setting!("fullscreen", WindowSettings::fullscreen);
+    register_nvim_setting!("exclusive_fullscreen", WindowSettings::exclusive_fullscreen);
+    register_nvim_setting!(
+        "macos_simple_fullscreen",
+        WindowSettings::macos_simple_fullscreen
+    );
+    register_nvim_setting!(
+        "fullscreen_refresh_rate",
+        WindowSettings::fullscreen_refresh_rate
+    );
+    register_nvim_setting!("always_on_top", WindowSettings::always_on_top);
+    register_nvim_setting!("background_blur", WindowSettings::background_blur);
+    register_nvim_setting!("title_bar_theme", WindowSettings::title_bar_theme);
+    register_nvim_setting!("window_backdrop", WindowSettings::window_backdrop);
+    register_nvim_setting!("tray_icon", WindowSettings::tray_icon);
+    register_nvim_setting!("minimize_to_tray", WindowSettings::minimize_to_tray);
+    register_nvim_setting!("max_surface_size", WindowSettings::max_surface_size);
+    register_nvim_setting!("live_resize", WindowSettings::live_resize);
+    register_nvim_setting!("confirm_quit", WindowSettings::confirm_quit);
+    register_nvim_setting!("pointer_grab", WindowSettings::pointer_grab);
+    register_nvim_setting!("keyboard_grab", WindowSettings::keyboard_grab);
+    register_nvim_setting!("relative_mouse", WindowSettings::relative_mouse);
+    register_nvim_setting!("precise_frame_timing", WindowSettings::precise_frame_timing);
+    register_nvim_setting!("pause_rendering", WindowSettings::pause_rendering);
+    register_nvim_setting!("render_scale", WindowSettings::render_scale);
+    register_nvim_setting!("drag_threshold", WindowSettings::drag_threshold);
+    register_nvim_setting!(
+        "keep_grid_size_on_font_change",
+        WindowSettings::keep_grid_size_on_font_change
+    );
+    register_nvim_setting!("capture_mouse", WindowSettings::capture_mouse);
+    register_nvim_setting!("custom_cursor", WindowSettings::custom_cursor);
+    register_nvim_setting!(
+        "hide_mouse_when_typing",
+        WindowSettings::hide_mouse_when_typing
+    );
+    register_nvim_setting!("raw_key_events", WindowSettings::raw_key_events);
+    register_nvim_setting!("touch_events", WindowSettings::touch_events);
+    register_nvim_setting!("key_repeat", WindowSettings::key_repeat);
+    register_nvim_setting!("key_repeat_delay", WindowSettings::key_repeat_delay);
+    register_nvim_setting!("key_repeat_interval", WindowSettings::key_repeat_interval);
+    register_nvim_setting!("touch_input", WindowSettings::touch_input);
+    register_nvim_setting!("max_draw_rate", WindowSettings::max_draw_rate);
+    register_nvim_setting!("frame_skip_policy", WindowSettings::frame_skip_policy);
+    register_nvim_setting!(
+        "redraw_on_window_events",
+        WindowSettings::redraw_on_window_events
+    );
+    register_nvim_setting!("game_controller", WindowSettings::game_controller);
+    register_nvim_setting!("remember_geometry", WindowSettings::remember_geometry);
+    register_nvim_setting!("close_action", WindowSettings::close_action);
+    register_nvim_setting!("frame_stats_interval", WindowSettings::frame_stats_interval);
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoopStats {
+    pub frames_drawn: u64,
+    pub dropped_frames: u64,
+    pub total_duration: Duration,
+    pub min_frame_time: Duration,
+    pub max_frame_time: Duration,
+    pub average_frame_time: Duration,
+}
+
+impl LoopStats {
+    fn record_frame(&mut self, frame_time: Duration) {
+        if self.frames_drawn == 0 || frame_time < self.min_frame_time {
+            self.min_frame_time = frame_time;
+        }
+        if frame_time > self.max_frame_time {
+            self.max_frame_time = frame_time;
+        }
+        self.frames_drawn += 1;
+        self.total_duration += frame_time;
+        self.average_frame_time = self.total_duration / self.frames_drawn as u32;
+    }
+}
+
+impl fmt::Display for LoopStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames in {:?} ({} dropped), frame time min {:?} max {:?} average {:?}",
+            self.frames_drawn,
+            self.total_duration,
+            self.dropped_frames,
+            self.min_frame_time,
+            self.max_frame_time,
+            self.average_frame_time
+        )
+    }
+}
+
+// Where the time went in the frames since the last report. Sent to neovim every
+// g:neovide_frame_stats_interval frames, or when asked with neovide.get_frame_stats.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub frames: u64,
+    pub frames_drawn: u64,
+    pub event_time: Duration,
+    pub draw_time: Duration,
+    // Acquiring the swapchain image, submitting and presenting
+    pub present_time: Duration,
+    pub longest_frame: Duration,
+    pub missed_deadlines: u64,
+    // From the earliest input before a drawn frame until it was presented. Neovim answers
+    // asynchronously, so its own changes may only show a frame or two later.
+    pub input_frames: u64,
+    pub input_latency: Duration,
+}
+
+impl FrameStats {
+    // Times are averages in milliseconds, except for the longest frame
+    fn to_value(&self) -> Value {
+        let average = |total: Duration, count: u64| {
+            if count == 0 {
+                0.0
+            } else {
+                total.as_secs_f64() * 1000.0 / count as f64
+            }
+        };
+        Value::Map(vec![
+            (Value::from("frames"), Value::from(self.frames)),
+            (Value::from("frames_drawn"), Value::from(self.frames_drawn)),
+            (
+                Value::from("event_time"),
+                Value::from(average(self.event_time, self.frames)),
+            ),
+            (
+                Value::from("draw_time"),
+                Value::from(average(self.draw_time, self.frames_drawn)),
+            ),
+            (
+                Value::from("present_time"),
+                Value::from(average(self.present_time, self.frames_drawn)),
+            ),
+            (
+                Value::from("longest_frame"),
+                Value::from(self.longest_frame.as_secs_f64() * 1000.0),
+            ),
+            (
+                Value::from("missed_deadlines"),
+                Value::from(self.missed_deadlines),
+            ),
+            (
+                Value::from("input_latency"),
+                Value::from(average(self.input_latency, self.input_frames)),
+            ),
+        ])
+    }
+}
+
+// The windowed bounds saved while fullscreen. The cached ones are from when fullscreen was entered
+// and are still zero if it never went through toggle_fullscreen.
+fn fullscreen_bounds(
+    cached: ((u32, u32), (i32, i32)),
+    windowed: ((u32, u32), (i32, i32)),
+) -> ((u32, u32), (i32, i32)) {
+    let ((width, height), _) = cached;
+    if width == 0 || height == 0 {
+        windowed
+    } else {
+        cached
+    }
+}
+
+fn benchmark_frames() -> Option<u64> {
+    let prefix = "--benchmark-frames=";
+
+    std::env::args()
+        .find(|arg| arg.starts_with(prefix))
+        .and_then(|arg| arg[prefix.len()..].parse().ok())
+}
+
+pub fn ui_loop() -> Result<(), WindowError> {
+    let config = command_line_config();
+    if let Some(frame_count) = benchmark_frames() {
+        println!("{}", ui_loop_frames(&config, frame_count)?);
+        return Ok(());
+    }
+
+    // Every event is already traced as it arrives
+    ui_loop_with_event_hook(&config, |_| {})
+}
+
+// Events neovide doesn't handle itself, such as custom SDL user events or subsystems it doesn't
+// wrap, are passed to the hook instead of being dropped. Hooks are FnMut and called only from the
+// loop's thread, so they can keep their own state without a RefCell or Mutex.
+pub fn ui_loop_with_event_hook(
+    config: &WindowConfig,
+    on_unhandled_event: impl FnMut(&Event),
+) -> Result<(), WindowError> {
+    ui_loop_with_hooks(config, on_unhandled_event, |_| {}, None)
+}
+
+// The tick hook runs once every frame before drawing with the time since the previous frame,
+// whether or not SDL delivered any events, so work arriving from outside SDL can be pumped there.
+// The draw handler is also used for frames drawn outside the loop, such as during a live resize.
+// Returns once neovim has exited, after the window is destroyed, rather than ending the process.
+pub fn ui_loop_with_hooks(
+    config: &WindowConfig,
+    on_unhandled_event: impl FnMut(&Event),
+    on_tick: impl FnMut(Duration),
+    draw_handler: Option<Box<dyn DrawHandler>>,
+) -> Result<(), WindowError> {
+    run_loop(config, on_unhandled_event, on_tick, draw_handler, None)?;
+    Ok(())
+}
+
+// Runs at most frame_count frames and returns their timings instead of exiting, so frame pacing
+// can be measured
+pub fn ui_loop_frames(config: &WindowConfig, frame_count: u64) -> Result<LoopStats, WindowError> {
+    run_loop(config, |_| {}, |_| {}, None, Some(frame_count))
 }
 
-pub fn ui_loop() {
-    let mut window = WindowWrapper::new();
+fn run_loop(
+    config: &WindowConfig,
+    mut on_unhandled_event: impl FnMut(&Event),
+    mut on_tick: impl FnMut(Duration),
+    draw_handler: Option<Box<dyn DrawHandler>>,
+    frame_count: Option<u64>,
+) -> Result<LoopStats, WindowError> {
+    // Shared with the live resize watch. The loop lets go of the window while it polls, which is
+    // the only time the watch runs.
+    let shared_window = Rc::new(RefCell::new(WindowWrapper::new(config)?));
+    let mut window = shared_window.borrow_mut();
+    window.draw_handler = draw_handler;
 
     info!("Starting window event loop");
+    // SDL only allows a single event pump per process, so this fails if something else in the
+    // process already holds one
     let mut event_pump = window
         .context
         .event_pump()
-        .expect("Could not create sdl event pump");
+        .map_err(WindowError::EventPump)?;
 
-    loop {
+    #[cfg(target_os = "windows")]
+    windows_set_timer_resolution(true);
+
+    let wake_event_type = match window
+        .context
+        .event()
+        .and_then(|event_subsystem| unsafe { event_subsystem.register_event() })
+    {
+        Ok(event_type) => {
+            REDRAW_SCHEDULER.set_waker(Some(Box::new(move || push_wake_event(event_type))));
+            Some(event_type)
+        }
+        Err(error) => {
+            warn!(
+                "Could not register the wake event, idling is disabled: {}",
+                error
+            );
+            None
+        }
+    };
+
+    // Windows runs its own modal loop while the window is dragged or resized, so polling stops
+    // until the user lets go. Event watches still fire from inside that loop, which keeps the
+    // content drawn at the new size.
+    #[cfg(target_os = "windows")]
+    let _live_resize_watch = if SETTINGS.get::<WindowSettings>().live_resize {
+        let watched_window = Rc::clone(&shared_window);
+        match window.context.event() {
+            Ok(event_subsystem) => Some(event_subsystem.add_event_watch(move |event: Event| {
+                if let Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } = event
+                {
+                    // Resizes outside of a poll are left for the loop to handle
+                    if let Ok(mut window) = watched_window.try_borrow_mut() {
+                        window.handle_live_resize();
+                    }
+                }
+            })),
+            Err(error) => {
+                warn!("Could not watch live resize events: {}", error);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut previous_frame_start = Instant::now();
+    let mut frame_deadline = previous_frame_start;
+    let mut stats = LoopStats::default();
+    let mut pending_event = None;
+    drop(window);
+
+    while frame_count.map_or(true, |frame_count| stats.frames_drawn < frame_count) {
+        let mut window = shared_window.borrow_mut();
         let frame_start = Instant::now();
+        let frame_interval = frame_start - previous_frame_start;
+        previous_frame_start = frame_start;
+        // Log lines are prefixed with the frame number, and events also get a sequence number, so
+        // cause and effect can be lined up in bug reports
+        window.frame_number += 1;
+        trace!(
+            "[frame {}] Frame interval: {:?}",
+            window.frame_number,
+            frame_interval
+        );
 
+        let commands_span = profile_span!("window_commands");
+        for command in WINDOW_COMMANDS.drain() {
+            window.handle_window_command(command);
+        }
+        for event in take_tray_events() {
+            window.handle_tray_event(event);
+        }
         window.synchronize_settings();
+        drop(commands_span);
 
         let mut keyboard_inputs = Vec::new();
 
         let mut keycode = None;
+        let mut scancode = None;
         let mut keytext = None;
         let mut ignore_text_this_frame = false;
+        let mut skip_repeated_text = false;
+
+        let events_span = profile_span!("events");
+        let events_start = Instant::now();
+        let window_id = window.window.id();
+        let injected_events = drain_injected_events()
+            .into_iter()
+            .map(|event| event.into_sdl_event(window_id));
+        drop(window);
+        let polled_events: Vec<Event> = event_pump.poll_iter().collect();
+        let mut window = shared_window.borrow_mut();
+        for event in pending_event
+            .take()
+            .into_iter()
+            .chain(injected_events)
+            .chain(polled_events)
+        {
+            window.event_number += 1;
+            trace!(
+                "[frame {}] Event {} received {:?} ago: {:?}",
+                window.frame_number,
+                window.event_number,
+                window.event_time(event.get_timestamp()).elapsed(),
+                event
+            );
+            if matches!(
+                event,
+                Event::KeyDown { .. }
+                    | Event::KeyUp { .. }
+                    | Event::TextInput { .. }
+                    | Event::MouseMotion { .. }
+                    | Event::MouseButtonDown { .. }
+                    | Event::MouseButtonUp { .. }
+                    | Event::MouseWheel { .. }
+                    | Event::FingerDown { .. }
+                    | Event::FingerMotion { .. }
+                    | Event::FingerUp { .. }
+            ) {
+                window.handle_input_time(event.get_timestamp());
+            }
 
-        for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => window.handle_quit(),
+                Event::User { type_, .. } if Some(type_) == wake_event_type => {}
+                Event::AppTerminating { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::Terminating)
+                }
+                Event::AppLowMemory { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::LowMemory)
+                }
+                Event::AppWillEnterBackground { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::WillEnterBackground)
+                }
+                Event::AppDidEnterBackground { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::DidEnterBackground)
+                }
+                Event::AppWillEnterForeground { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::WillEnterForeground)
+                }
+                Event::AppDidEnterForeground { .. } => {
+                    window.handle_app_lifecycle(AppLifecycleState::DidEnterForeground)
+                }
+                Event::KeymapChanged { .. } => window.handle_keymap_changed(),
+                Event::Display {
+                    display_index,
+                    display_event: DisplayEvent::Connected,
+                    ..
+                } => window.handle_display_connected(display_index),
+                Event::Display {
+                    display_index,
+                    display_event: DisplayEvent::Disconnected,
+                    ..
+                } => window.handle_display_disconnected(display_index),
+                // Only announced, the text itself is sent when neovim asks for it
+                Event::ClipboardUpdate { .. } => BRIDGE.queue_command(UiCommand::UserEvent(
+                    String::from("NeovideClipboardChanged"),
+                )),
+                Event::DropBegin { .. } => window.handle_drop_begin(),
                 Event::DropFile { filename, .. } => {
                     BRIDGE.queue_command(UiCommand::FileDrop(filename));
                 }
+                Event::DropComplete { .. } => window.handle_drop_complete(),
                 Event::KeyDown {
                     keycode: received_keycode,
+                    scancode: received_scancode,
+                    repeat,
                     ..
                 } => {
+                    window.handle_raw_key(true, received_keycode, received_scancode, repeat);
+
+                    if repeat && window.handles_key_repeat() {
+                        skip_repeated_text = true;
+                        continue;
+                    }
+
                     // If keycode has a value, add it to the list as the new keycode supercedes
                     // this one.
                     if keycode.is_some() {
-                        keyboard_inputs.push((keycode, None));
+                        keyboard_inputs.push((keycode, scancode, None));
                     }
 
                     keycode = received_keycode;
+                    scancode = received_scancode;
+                }
+                Event::KeyUp {
+                    keycode: received_keycode,
+                    scancode: received_scancode,
+                    repeat,
+                    ..
+                } => {
+                    window.handle_raw_key(false, received_keycode, received_scancode, repeat);
+                    window.handle_key_up(received_scancode);
                 }
+                // The text of a repeat follows its key down event
+                Event::TextInput { .. } if skip_repeated_text => skip_repeated_text = false,
                 Event::TextInput { text, .. } => {
                     // If keycode has a value, add it to the list as the new keycode supercedes
                     // this one.
                     if keytext.is_some() {
-                        keyboard_inputs.push((None, keytext));
+                        keyboard_inputs.push((None, None, keytext));
                     }
 
                     keytext = Some(text);
                 }
+                Event::TextEditing {
+                    text,
+                    start,
+                    length,
+                    ..
+                } => window.handle_ime_preedit(text, start, length),
                 Event::MouseMotion { x, y, .. } => window.handle_pointer_motion(x, y),
-                Event::MouseButtonDown { .. } => window.handle_pointer_down(),
-                Event::MouseButtonUp { .. } => window.handle_pointer_up(),
+                Event::MouseButtonDown { mouse_btn, .. } => window.handle_pointer_down(mouse_btn),
+                Event::MouseButtonUp { mouse_btn, .. } => window.handle_pointer_up(mouse_btn),
                 Event::MouseWheel { x, y, .. } => window.handle_mouse_wheel(x, y),
+                Event::FingerDown {
+                    finger_id,
+                    x,
+                    y,
+                    pressure,
+                    ..
+                } => window.handle_touch(finger_id, x, y, pressure, TouchPhase::Started),
+                Event::FingerMotion {
+                    finger_id,
+                    x,
+                    y,
+                    pressure,
+                    ..
+                } => window.handle_touch(finger_id, x, y, pressure, TouchPhase::Moved),
+                Event::FingerUp {
+                    finger_id,
+                    x,
+                    y,
+                    pressure,
+                    ..
+                } => window.handle_touch(finger_id, x, y, pressure, TouchPhase::Ended),
+                Event::MultiGesture {
+                    d_theta,
+                    d_dist,
+                    x,
+                    y,
+                    num_fingers,
+                    ..
+                } => window.handle_multi_gesture(d_theta, d_dist, x, y, num_fingers),
+                Event::ControllerDeviceAdded { which, .. } => window.handle_controller_added(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    window.handle_controller_removed(which)
+                }
+                Event::ControllerButtonDown { which, button, .. } => {
+                    window.handle_controller_button(which, button, true)
+                }
+                Event::ControllerButtonUp { which, button, .. } => {
+                    window.handle_controller_button(which, button, false)
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => window.handle_controller_axis(which, axis, value),
                 Event::Window {
                     win_event: WindowEvent::FocusLost,
                     ..
@@ -460,39 +3167,319 @@ pub fn ui_loop() {
                     ignore_text_this_frame = true; // Ignore any text events on the first frame when focus is regained. https://github.com/Kethku/neovide/issues/193
                     window.handle_focus_gained();
                 }
-                Event::Window { .. } => REDRAW_SCHEDULER.queue_next_frame(),
-                _ => {}
+                Event::Window {
+                    win_event: WindowEvent::Shown,
+                    ..
+                } => window.handle_shown(),
+                Event::Window {
+                    win_event: WindowEvent::Maximized,
+                    ..
+                } => window.handle_maximized(),
+                Event::Window {
+                    win_event: WindowEvent::Minimized,
+                    ..
+                } => window.handle_minimized(),
+                Event::Window {
+                    win_event: WindowEvent::Restored,
+                    ..
+                } => window.handle_restored(),
+                Event::Window {
+                    win_event: WindowEvent::Enter,
+                    ..
+                } => window.handle_pointer_enter(),
+                Event::Window {
+                    win_event: WindowEvent::Leave,
+                    ..
+                } => window.handle_pointer_leave(),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    window.handle_native_fullscreen_change();
+                    REDRAW_SCHEDULER.queue_next_frame();
+                }
+                Event::Window {
+                    win_event: WindowEvent::Exposed | WindowEvent::Resized(..),
+                    ..
+                } => REDRAW_SCHEDULER.queue_next_frame(),
+                // Some window managers send a flood of events while the window is dragged that
+                // don't change what is visible, so only the ones above redraw by default
+                Event::Window { .. } => {
+                    if SETTINGS.get::<WindowSettings>().redraw_on_window_events {
+                        REDRAW_SCHEDULER.queue_next_frame();
+                    }
+                }
+                event => on_unhandled_event(&event),
             }
 
             // If both keycode and keytext have values, then add them to the list and reset the
             // variables.
             if keycode.is_some() && keytext.is_some() {
-                keyboard_inputs.push((keycode, keytext));
+                keyboard_inputs.push((keycode, scancode, keytext));
                 keycode = None;
+                scancode = None;
                 keytext = None;
             }
         }
 
-        keyboard_inputs.push((keycode, keytext));
+        keyboard_inputs.push((keycode, scancode, keytext));
 
         if !ignore_text_this_frame {
-            for (keycode, keytext) in keyboard_inputs.into_iter() {
-                window.handle_keyboard_input(keycode, keytext);
+            for (keycode, scancode, keytext) in keyboard_inputs.into_iter() {
+                window.handle_keyboard_input(keycode, scancode, keytext);
             }
         }
 
+        window.repeat_held_key();
+        window.frame_stats.event_time += events_start.elapsed();
+        drop(events_span);
+
+        on_tick(frame_interval);
+
+        // Benchmarks measure drawing, so every iteration draws even if nothing changed
+        if frame_count.is_some() {
+            REDRAW_SCHEDULER.queue_next_frame();
+        }
+        let frames_drawn = window.frame_stats.frames_drawn;
         if !window.draw_frame() {
             break;
         }
+        // Iterations that only handled events or were capped don't count as frames
+        let drew = window.frame_stats.frames_drawn > frames_drawn;
+
+        let settings = SETTINGS.get::<WindowSettings>();
+        let frame_length = Duration::from_secs_f32(1.0 / settings.refresh_rate as f32);
+
+        window.frame_stats.frames += 1;
+        if Instant::now() > frame_deadline + frame_length {
+            window.frame_stats.missed_deadlines += 1;
+        }
+        window.frame_stats.longest_frame =
+            window.frame_stats.longest_frame.max(frame_start.elapsed());
+        if settings.frame_stats_interval > 0
+            && window.frame_stats.frames >= settings.frame_stats_interval
+        {
+            window.report_frame_stats();
+        }
+
+        let (next_deadline, skipped) = next_frame_deadline(
+            &settings.frame_skip_policy,
+            frame_deadline + frame_length,
+            Instant::now(),
+            frame_length,
+        );
+        if skipped > 0 {
+            trace!(
+                "[frame {}] Frame over budget, skipped {} frames",
+                window.frame_number,
+                skipped
+            );
+            stats.dropped_frames += skipped as u64;
+        }
+        frame_deadline = next_deadline;
+
+        // Benchmarks draw every frame, so they never idle
+        if wake_event_type.is_some() && frame_count.is_none() && window.can_idle() {
+            REDRAW_SCHEDULER.start_idle();
+            let wait_limit = Instant::now() + IDLE_WAIT_LIMIT;
+            let idle_deadline = REDRAW_SCHEDULER
+                .next_frame()
+                .map_or(wait_limit, |next_frame| next_frame.min(wait_limit));
+            if idle_deadline > frame_deadline {
+                if drew {
+                    stats.record_frame(frame_start.elapsed());
+                }
+                let timeout = idle_deadline.saturating_duration_since(Instant::now());
+                trace!(
+                    "[frame {}] Idle for up to {:?}",
+                    window.frame_number,
+                    timeout
+                );
+                drop(window);
+                pending_event = event_pump.wait_event_timeout(timeout.as_millis() as u32);
+                REDRAW_SCHEDULER.stop_idle();
+                // Time spent waiting doesn't count as skipped frames
+                frame_deadline = Instant::now();
+                continue;
+            }
+            REDRAW_SCHEDULER.stop_idle();
+        }
+
+        sleep_until(frame_deadline, settings.precise_frame_timing);
+        if drew {
+            stats.record_frame(frame_start.elapsed());
+        }
+    }
+
+    debug!("Dropped {} frames", stats.dropped_frames);
+
+    REDRAW_SCHEDULER.set_waker(None);
+
+    shared_window.borrow_mut().save_geometry();
+
+    #[cfg(target_os = "windows")]
+    windows_set_timer_resolution(false);
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drag_continues_when_later_button_released() {
+        let mut buttons = PressedButtons::default();
+        buttons.press(MouseButton::Left);
+        buttons.press(MouseButton::Right);
+        buttons.release(MouseButton::Right);
 
-        let elapsed = frame_start.elapsed();
-        let refresh_rate = { SETTINGS.get::<WindowSettings>().refresh_rate as f32 };
-        let frame_length = Duration::from_secs_f32(1.0 / refresh_rate);
+        assert_eq!(buttons.drag_button(), Some(MouseButton::Left));
+    }
+
+    #[test]
+    fn test_normalize_axis() {
+        assert_eq!(normalize_axis(0), 0.0);
+        assert_eq!(normalize_axis(CONTROLLER_AXIS_DEADZONE - 1), 0.0);
+        assert_eq!(normalize_axis(i16::MAX), 1.0);
+        assert_eq!(normalize_axis(i16::MIN), -1.0);
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let data = HitTestData {
+            drag_regions: vec![(0, 0, 10, 1)],
+            cell_size: (10.0, 20.0),
+            resize_border: true,
+        };
+        let size = (200, 100);
+        assert_eq!(
+            hit_test(&data, (50, 10), size),
+            SDL_HitTestResult::SDL_HITTEST_DRAGGABLE
+        );
+        assert_eq!(
+            hit_test(&data, (150, 10), size),
+            SDL_HitTestResult::SDL_HITTEST_NORMAL
+        );
+        assert_eq!(
+            hit_test(&data, (0, 0), size),
+            SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPLEFT
+        );
+        assert_eq!(
+            hit_test(&data, (199, 50), size),
+            SDL_HitTestResult::SDL_HITTEST_RESIZE_RIGHT
+        );
+    }
+
+    #[test]
+    fn test_gesture_steps() {
+        let mut gestures = GestureTracker::default();
+        assert!(gestures.update(0.0, 0.0, (0.5, 0.5), 2).is_empty());
+        assert_eq!(
+            gestures.update(0.0, 0.06, (0.5, 0.5), 2),
+            vec![String::from("NeovideZoomIn")]
+        );
+        assert_eq!(
+            gestures.update(-0.4, 0.0, (0.5, 0.5), 2),
+            vec![String::from("NeovideRotateCounterClockwise")]
+        );
+        assert_eq!(
+            gestures.update(0.0, 0.0, (0.3, 0.55), 2),
+            vec![String::from("NeovideSwipe2Left")]
+        );
+
+        // A different number of fingers starts a new gesture from where they are
+        assert!(gestures.update(0.0, 0.0, (0.9, 0.9), 3).is_empty());
+        assert_eq!(
+            gestures.update(0.0, 0.0, (0.9, 0.7), 3),
+            vec![String::from("NeovideSwipe3Up")]
+        );
+    }
+
+    #[test]
+    fn test_loop_stats_frame_times() {
+        let mut stats = LoopStats::default();
+        stats.record_frame(Duration::from_millis(20));
+        stats.record_frame(Duration::from_millis(10));
+        stats.record_frame(Duration::from_millis(30));
+
+        assert_eq!(stats.frames_drawn, 3);
+        assert_eq!(stats.total_duration, Duration::from_millis(60));
+        assert_eq!(stats.min_frame_time, Duration::from_millis(10));
+        assert_eq!(stats.max_frame_time, Duration::from_millis(30));
+        assert_eq!(stats.average_frame_time, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_frame_stats_average_input_latency() {
+        let frame_stats = FrameStats {
+            input_frames: 2,
+            input_latency: Duration::from_millis(30),
+            ..FrameStats::default()
+        };
+        let value = frame_stats.to_value();
+        let input_latency = value
+            .as_map()
+            .unwrap()
+            .iter()
+            .find(|(key, _)| key.as_str() == Some("input_latency"))
+            .and_then(|(_, value)| value.as_f64());
+
+        assert_eq!(input_latency, Some(15.0));
+    }
 
-        if elapsed < frame_length {
-            sleep(frame_length - elapsed);
+    #[test]
+    fn test_fullscreen_geometry_keeps_windowed_size() {
+        let windowed = ((800, 600), (40, 30));
+        for cached in &[((0, 0), (0, 0)), windowed] {
+            let (size, position) = fullscreen_bounds(*cached, windowed);
+            let geometry = WindowGeometry {
+                size,
+                position,
+                maximized: false,
+                fullscreen: Some(FullscreenMode::Desktop { display: 1 }),
+            };
+            let restored: WindowGeometry = geometry.to_string().parse().unwrap();
+
+            assert_eq!(restored, geometry);
+            assert_eq!(restored.size, (800, 600));
         }
     }
 
-    std::process::exit(0);
+    #[test]
+    fn test_frame_skip_policies() {
+        let frame_length = Duration::from_millis(10);
+        let deadline = Instant::now();
+        let now = deadline + Duration::from_millis(25);
+
+        assert_eq!(
+            next_frame_deadline(&FrameSkipPolicy::None, deadline, now, frame_length),
+            (now, 0)
+        );
+        assert_eq!(
+            next_frame_deadline(&FrameSkipPolicy::SkipToLatest, deadline, now, frame_length),
+            (deadline + Duration::from_millis(30), 3)
+        );
+        assert_eq!(
+            next_frame_deadline(&FrameSkipPolicy::FixedStep, deadline, now, frame_length),
+            (deadline, 0)
+        );
+        assert_eq!(
+            next_frame_deadline(&FrameSkipPolicy::SkipToLatest, now, deadline, frame_length),
+            (now, 0)
+        );
+    }
+
+    #[test]
+    fn test_drag_moves_to_remaining_button_when_first_released() {
+        let mut buttons = PressedButtons::default();
+        buttons.press(MouseButton::Left);
+        buttons.press(MouseButton::Right);
+
+        buttons.release(MouseButton::Left);
+        assert_eq!(buttons.drag_button(), Some(MouseButton::Right));
+
+        buttons.release(MouseButton::Right);
+        assert_eq!(buttons.drag_button(), None);
+    }
 }
@@ -0,0 +1,93 @@
+use skulpin::sdl2::sys::{SDL_GetWindowSurface, SDL_PixelFormatEnum, SDL_UpdateWindowSurface};
+use skulpin::sdl2::video::Window;
+use skulpin::skia_safe::{AlphaType, Canvas, ColorType, ImageInfo, Surface};
+
+// Draws on the CPU straight into the window's own surface. Slow, but it needs no graphics driver
+// at all, so the window still comes up on headless servers, in CI and with broken GPU drivers.
+pub struct SoftwareRenderer;
+
+impl SoftwareRenderer {
+    pub fn new(window: &Window) -> Result<SoftwareRenderer, String> {
+        // Fails early if the window can't have a surface, so the next backend can be tried
+        unsafe { window_surface(window)? };
+        Ok(SoftwareRenderer)
+    }
+
+    pub fn draw(&mut self, window: &Window, draw: impl FnOnce(&mut Canvas)) -> Result<(), String> {
+        // SDL replaces the window surface when the window is resized, so it is fetched every frame
+        let window_surface = unsafe { window_surface(window)? };
+        // Skia names formats by byte order and SDL by packed value, so on little endian machines
+        // SDL's ARGB is Skia's BGRA
+        let format = window_surface.format;
+        let color_type = if format == SDL_PixelFormatEnum::SDL_PIXELFORMAT_ARGB8888 as u32
+            || format == SDL_PixelFormatEnum::SDL_PIXELFORMAT_RGB888 as u32
+        {
+            ColorType::BGRA8888
+        } else if format == SDL_PixelFormatEnum::SDL_PIXELFORMAT_ABGR8888 as u32
+            || format == SDL_PixelFormatEnum::SDL_PIXELFORMAT_BGR888 as u32
+        {
+            ColorType::RGBA8888
+        } else {
+            return Err(format!("Unsupported window surface format {:#x}", format));
+        };
+        let image_info = ImageInfo::new(
+            (window_surface.width, window_surface.height),
+            color_type,
+            AlphaType::Premul,
+            None,
+        );
+
+        {
+            let mut surface = Surface::new_raster_direct(
+                &image_info,
+                window_surface.pixels,
+                window_surface.pitch,
+                None,
+            )
+            .ok_or_else(|| String::from("Could not wrap the window surface"))?;
+
+            let (logical_width, _) = window.size();
+            let scale_factor = window_surface.width as f32 / logical_width.max(1) as f32;
+            let canvas = surface.canvas();
+            canvas.reset_matrix();
+            canvas.scale((scale_factor, scale_factor));
+            draw(canvas);
+        }
+
+        if unsafe { SDL_UpdateWindowSurface(window.raw()) } != 0 {
+            return Err(skulpin::sdl2::get_error());
+        }
+        Ok(())
+    }
+}
+
+struct WindowSurface<'a> {
+    width: i32,
+    height: i32,
+    pitch: usize,
+    format: u32,
+    pixels: &'a mut [u8],
+}
+
+// The sdl2 crate only hands out the window surface together with the event pump, which the
+// renderer doesn't have, so it is fetched directly. The pixels stay valid until the window is
+// resized, which can't happen while a frame is being drawn.
+unsafe fn window_surface(window: &Window) -> Result<WindowSurface, String> {
+    let surface = SDL_GetWindowSurface(window.raw());
+    if surface.is_null() {
+        return Err(skulpin::sdl2::get_error());
+    }
+    let surface = &*surface;
+    let format = (*surface.format).format;
+    let pitch = surface.pitch as usize;
+    Ok(WindowSurface {
+        width: surface.w,
+        height: surface.h,
+        pitch,
+        format,
+        pixels: std::slice::from_raw_parts_mut(
+            surface.pixels as *mut u8,
+            pitch * surface.h as usize,
+        ),
+    })
+}
@@ -0,0 +1,234 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::error;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use rmpv::Value;
+
+use crate::settings::FromValue;
+
+static THEME_CHANGED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TitleBarTheme {
+    // Follows the system's app theme, including when it changes
+    Auto,
+    Dark,
+    Light,
+}
+
+impl FromValue for TitleBarTheme {
+    fn from_value(&mut self, value: Value) {
+        match value.as_str() {
+            Some("auto") => *self = TitleBarTheme::Auto,
+            Some("dark") => *self = TitleBarTheme::Dark,
+            Some("light") => *self = TitleBarTheme::Light,
+            _ => error!(
+                "title_bar_theme setting expected one of auto, dark or light, but received: {}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<TitleBarTheme> for Value {
+    fn from(theme: TitleBarTheme) -> Self {
+        match theme {
+            TitleBarTheme::Auto => "auto".into(),
+            TitleBarTheme::Dark => "dark".into(),
+            TitleBarTheme::Light => "light".into(),
+        }
+    }
+}
+
+impl TitleBarTheme {
+    pub fn is_dark(self) -> bool {
+        match self {
+            TitleBarTheme::Auto => system_prefers_dark(),
+            TitleBarTheme::Dark => true,
+            TitleBarTheme::Light => false,
+        }
+    }
+}
+
+// The material Windows 11 draws behind the title bar and any transparent parts of the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowBackdrop {
+    None,
+    Mica,
+    Acrylic,
+    Tabbed,
+}
+
+impl FromValue for WindowBackdrop {
+    fn from_value(&mut self, value: Value) {
+        match value.as_str() {
+            Some("none") => *self = WindowBackdrop::None,
+            Some("mica") => *self = WindowBackdrop::Mica,
+            Some("acrylic") => *self = WindowBackdrop::Acrylic,
+            Some("tabbed") => *self = WindowBackdrop::Tabbed,
+            _ => error!(
+                "window_backdrop setting expected one of none, mica, acrylic or tabbed, but received: {}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<WindowBackdrop> for Value {
+    fn from(backdrop: WindowBackdrop) -> Self {
+        match backdrop {
+            WindowBackdrop::None => "none".into(),
+            WindowBackdrop::Mica => "mica".into(),
+            WindowBackdrop::Acrylic => "acrylic".into(),
+            WindowBackdrop::Tabbed => "tabbed".into(),
+        }
+    }
+}
+
+// Only Windows 10 from build 18985 and Windows 11 draw a dark title bar. Elsewhere the title bar
+// follows the system theme on its own and an error is returned.
+pub fn set_title_bar_theme(window: &impl HasRawWindowHandle, dark: bool) -> Result<(), String> {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Windows(handle) => {
+            // Builds before 20H1 used an undocumented attribute one lower
+            const DWMWA_USE_IMMERSIVE_DARK_MODE: u32 = 20;
+            const DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1: u32 = 19;
+
+            windows_set_attribute(handle.hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE, dark as u32).or_else(
+                |_| {
+                    windows_set_attribute(
+                        handle.hwnd,
+                        DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1,
+                        dark as u32,
+                    )
+                },
+            )
+        }
+        _ => Err(String::from(
+            "The title bar theme is not supported by this window system",
+        )),
+    }
+}
+
+// Needs Windows 11 build 22621 or later
+pub fn set_window_backdrop(
+    window: &impl HasRawWindowHandle,
+    backdrop: WindowBackdrop,
+) -> Result<(), String> {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Windows(handle) => {
+            const DWMWA_SYSTEMBACKDROP_TYPE: u32 = 38;
+
+            let backdrop_type = match backdrop {
+                WindowBackdrop::None => 1,
+                WindowBackdrop::Mica => 2,
+                WindowBackdrop::Acrylic => 3,
+                WindowBackdrop::Tabbed => 4,
+            };
+            windows_set_attribute(handle.hwnd, DWMWA_SYSTEMBACKDROP_TYPE, backdrop_type)
+        }
+        _ => Err(String::from(
+            "Window backdrops are not supported by this window system",
+        )),
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_set_attribute(
+    hwnd: *mut std::ffi::c_void,
+    attribute: u32,
+    value: u32,
+) -> Result<(), String> {
+    use winapi::shared::windef::HWND;
+    use winapi::um::dwmapi::DwmSetWindowAttribute;
+
+    let result = unsafe {
+        DwmSetWindowAttribute(
+            hwnd as HWND,
+            attribute,
+            &value as *const u32 as *const _,
+            std::mem::size_of::<u32>() as u32,
+        )
+    };
+    if result < 0 {
+        return Err(format!(
+            "DwmSetWindowAttribute {} failed: {:#x}",
+            attribute, result
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn system_prefers_dark() -> bool {
+    use std::ffi::CString;
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::winreg::{RegGetValueA, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    let subkey =
+        CString::new("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize").unwrap();
+    let value_name = CString::new("AppsUseLightTheme").unwrap();
+    let mut light: DWORD = 1;
+    let mut size = std::mem::size_of::<DWORD>() as DWORD;
+    let result = unsafe {
+        RegGetValueA(
+            HKEY_CURRENT_USER,
+            subkey.as_ptr(),
+            value_name.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut light as *mut DWORD as *mut _,
+            &mut size,
+        )
+    };
+    // Missing before Windows 10 1809, which only had a light theme for apps
+    result == 0 && light == 0
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn system_prefers_dark() -> bool {
+    false
+}
+
+// Windows broadcasts a setting change named ImmersiveColorSet when the app theme is switched. SDL
+// hands every message to this hook before handling it, on the thread polling events. SDL only
+// takes one hook, so the tray icon's messages are passed on from here too.
+#[cfg(target_os = "windows")]
+unsafe extern "C" fn windows_message_hook(
+    _userdata: *mut std::ffi::c_void,
+    hwnd: *mut std::ffi::c_void,
+    message: std::os::raw::c_uint,
+    _wparam: u64,
+    lparam: i64,
+) {
+    use crate::redraw_scheduler::REDRAW_SCHEDULER;
+    use winapi::um::winuser::WM_SETTINGCHANGE;
+
+    super::handle_tray_message(hwnd, message, lparam as isize);
+    if message != WM_SETTINGCHANGE || lparam == 0 {
+        return;
+    }
+    let name = lparam as *const u16;
+    let length = (0..).take_while(|&index| *name.add(index) != 0).count();
+    if String::from_utf16_lossy(std::slice::from_raw_parts(name, length)) == "ImmersiveColorSet" {
+        THEME_CHANGED.store(true, Ordering::Relaxed);
+        REDRAW_SCHEDULER.wake();
+    }
+}
+
+pub fn watch_theme_changes() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        skulpin::sdl2::sys::SDL_SetWindowsMessageHook(
+            Some(windows_message_hook),
+            std::ptr::null_mut(),
+        );
+    }
+}
+
+// True once after each change of the system theme
+pub fn take_theme_changed() -> bool {
+    THEME_CHANGED.swap(false, Ordering::Relaxed)
+}
@@ -0,0 +1,297 @@
+use std::sync::Mutex;
+
+use raw_window_handle::HasRawWindowHandle;
+
+use crate::redraw_scheduler::REDRAW_SCHEDULER;
+#[cfg(target_os = "windows")]
+use crate::window::Urgency;
+
+lazy_static! {
+    static ref TRAY_EVENTS: Mutex<Vec<TrayEvent>> = Mutex::new(Vec::new());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrayEvent {
+    // The icon itself was clicked, or Show was chosen from its menu
+    Show,
+    Quit,
+}
+
+fn push_tray_event(event: TrayEvent) {
+    TRAY_EVENTS.lock().unwrap().push(event);
+    REDRAW_SCHEDULER.wake();
+}
+
+pub fn take_tray_events() -> Vec<TrayEvent> {
+    std::mem::take(&mut *TRAY_EVENTS.lock().unwrap())
+}
+
+// An icon in the Windows notification area or the macOS status bar, with a menu to show the
+// window again or quit. It is removed when dropped. Linux desktops only offer StatusNotifierItem
+// over DBus, which isn't supported yet, so creating one fails there.
+pub struct TrayIcon {
+    #[cfg(target_os = "windows")]
+    hwnd: *mut std::ffi::c_void,
+    #[cfg(target_os = "macos")]
+    status_item: cocoa::base::id,
+}
+
+impl TrayIcon {
+    pub fn new(window: &impl HasRawWindowHandle, tooltip: &str) -> Result<TrayIcon, String> {
+        #[allow(unreachable_patterns)]
+        match window.raw_window_handle() {
+            #[cfg(target_os = "windows")]
+            raw_window_handle::RawWindowHandle::Windows(handle) => {
+                windows::add_icon(handle.hwnd, tooltip)?;
+                Ok(TrayIcon { hwnd: handle.hwnd })
+            }
+            #[cfg(target_os = "macos")]
+            raw_window_handle::RawWindowHandle::MacOS(_) => Ok(TrayIcon {
+                status_item: macos::add_status_item(tooltip),
+            }),
+            _ => {
+                let _ = tooltip;
+                Err(String::from(
+                    "Tray icons are not supported on this platform",
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl TrayIcon {
+    // Windows 10 and later show the balloon as a toast, and keep it in the action center
+    pub fn show_balloon(&self, title: &str, body: &str, urgency: Urgency) -> Result<(), String> {
+        windows::show_balloon(self.hwnd, title, body, urgency)
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        #[cfg(target_os = "windows")]
+        windows::remove_icon(self.hwnd);
+        #[cfg(target_os = "macos")]
+        macos::remove_status_item(self.status_item);
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub use windows::handle_tray_message;
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use winapi::shared::minwindef::{LPARAM, UINT};
+    use winapi::shared::windef::{HICON, HWND, POINT};
+    use winapi::um::shellapi::{
+        Shell_NotifyIconW, NIF_ICON, NIF_INFO, NIF_MESSAGE, NIF_TIP, NIIF_INFO, NIIF_NOSOUND,
+        NIIF_WARNING, NIM_ADD, NIM_DELETE, NIM_MODIFY, NOTIFYICONDATAW,
+    };
+    use winapi::um::winuser::{
+        AppendMenuW, CreatePopupMenu, DestroyMenu, GetCursorPos, LoadIconW, SendMessageW,
+        SetForegroundWindow, TrackPopupMenu, ICON_SMALL, IDI_APPLICATION, MF_STRING, TPM_RETURNCMD,
+        TPM_RIGHTBUTTON, WM_APP, WM_GETICON, WM_LBUTTONUP, WM_RBUTTONUP,
+    };
+
+    use super::{push_tray_event, TrayEvent};
+    use crate::window::Urgency;
+
+    // Sent to the window by the shell for clicks on the icon
+    const TRAY_MESSAGE: UINT = WM_APP + 1;
+    const TRAY_ICON_ID: UINT = 1;
+    const MENU_SHOW: usize = 1;
+    const MENU_QUIT: usize = 2;
+
+    fn to_wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    // Copies as much of the text as fits, leaving room for the terminator
+    fn copy_wide(target: &mut [u16], text: &str) {
+        let text = to_wide(text);
+        let length = text.len().min(target.len() - 1);
+        target[..length].copy_from_slice(&text[..length]);
+    }
+
+    fn notify_icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+        let mut data: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+        data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        data.hWnd = hwnd;
+        data.uID = TRAY_ICON_ID;
+        data
+    }
+
+    pub fn add_icon(hwnd: *mut std::ffi::c_void, tooltip: &str) -> Result<(), String> {
+        let hwnd = hwnd as HWND;
+        let mut data = notify_icon_data(hwnd);
+        data.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+        data.uCallbackMessage = TRAY_MESSAGE;
+        unsafe {
+            // The window's own icon, which SDL sets from the icon asset
+            data.hIcon = SendMessageW(hwnd, WM_GETICON, ICON_SMALL as usize, 0) as HICON;
+            if data.hIcon.is_null() {
+                data.hIcon = LoadIconW(std::ptr::null_mut(), IDI_APPLICATION);
+            }
+        }
+        copy_wide(&mut data.szTip, tooltip);
+
+        if unsafe { Shell_NotifyIconW(NIM_ADD, &mut data) } == 0 {
+            return Err(String::from("Shell_NotifyIcon could not add the icon"));
+        }
+        Ok(())
+    }
+
+    pub fn show_balloon(
+        hwnd: *mut std::ffi::c_void,
+        title: &str,
+        body: &str,
+        urgency: Urgency,
+    ) -> Result<(), String> {
+        let mut data = notify_icon_data(hwnd as HWND);
+        data.uFlags = NIF_INFO;
+        copy_wide(&mut data.szInfoTitle, title);
+        copy_wide(&mut data.szInfo, body);
+        data.dwInfoFlags = match urgency {
+            Urgency::Low => NIIF_INFO | NIIF_NOSOUND,
+            Urgency::Normal => NIIF_INFO,
+            Urgency::Critical => NIIF_WARNING,
+        };
+        if unsafe { Shell_NotifyIconW(NIM_MODIFY, &mut data) } == 0 {
+            return Err(String::from(
+                "Shell_NotifyIcon could not show the notification",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn remove_icon(hwnd: *mut std::ffi::c_void) {
+        let mut data = notify_icon_data(hwnd as HWND);
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &mut data) };
+    }
+
+    // Called from the window's message hook. Left clicks show the window, right clicks open the
+    // menu, which runs its own loop until something is chosen.
+    pub fn handle_tray_message(hwnd: *mut std::ffi::c_void, message: UINT, lparam: LPARAM) {
+        if message != TRAY_MESSAGE {
+            return;
+        }
+        match lparam as UINT {
+            WM_LBUTTONUP => push_tray_event(TrayEvent::Show),
+            WM_RBUTTONUP => {
+                let hwnd = hwnd as HWND;
+                let show = to_wide("Show Neovide");
+                let quit = to_wide("Quit Neovide");
+                unsafe {
+                    let menu = CreatePopupMenu();
+                    AppendMenuW(menu, MF_STRING, MENU_SHOW, show.as_ptr());
+                    AppendMenuW(menu, MF_STRING, MENU_QUIT, quit.as_ptr());
+                    let mut cursor = POINT { x: 0, y: 0 };
+                    GetCursorPos(&mut cursor);
+                    // Without this the menu doesn't close when clicking elsewhere
+                    SetForegroundWindow(hwnd);
+                    let chosen = TrackPopupMenu(
+                        menu,
+                        TPM_RETURNCMD | TPM_RIGHTBUTTON,
+                        cursor.x,
+                        cursor.y,
+                        0,
+                        hwnd,
+                        std::ptr::null(),
+                    );
+                    DestroyMenu(menu);
+                    match chosen as usize {
+                        MENU_SHOW => push_tray_event(TrayEvent::Show),
+                        MENU_QUIT => push_tray_event(TrayEvent::Quit),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::sync::Once;
+
+    use cocoa::appkit::{NSMenu, NSMenuItem};
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::{NSSize, NSString};
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Sel};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    use super::{push_tray_event, TrayEvent};
+
+    const NS_VARIABLE_STATUS_ITEM_LENGTH: f64 = -1.0;
+
+    // Like the menu bar's, the actions are class methods so the target can't be freed
+    fn tray_target() -> id {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            let mut decl = ClassDecl::new("NeovideTrayTarget", class!(NSObject))
+                .expect("Could not declare the tray target class");
+            unsafe {
+                decl.add_class_method(sel!(show:), show as extern "C" fn(&Class, Sel, id));
+                decl.add_class_method(sel!(quit:), quit as extern "C" fn(&Class, Sel, id));
+            }
+            decl.register();
+        });
+        class!(NeovideTrayTarget) as *const Class as id
+    }
+
+    extern "C" fn show(_: &Class, _: Sel, _: id) {
+        push_tray_event(TrayEvent::Show);
+    }
+
+    extern "C" fn quit(_: &Class, _: Sel, _: id) {
+        push_tray_event(TrayEvent::Quit);
+    }
+
+    unsafe fn ns_string(string: &str) -> id {
+        NSString::alloc(nil).init_str(string).autorelease()
+    }
+
+    unsafe fn add_item(menu: id, title: &str, action: Sel) {
+        let item = NSMenuItem::alloc(nil)
+            .initWithTitle_action_keyEquivalent_(ns_string(title), action, ns_string(""))
+            .autorelease();
+        let _: () = msg_send![item, setTarget: tray_target()];
+        menu.addItem_(item);
+    }
+
+    pub fn add_status_item(tooltip: &str) -> id {
+        unsafe {
+            let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+            let status_item: id =
+                msg_send![status_bar, statusItemWithLength: NS_VARIABLE_STATUS_ITEM_LENGTH];
+            let _: () = msg_send![status_item, retain];
+
+            // The app icon, shrunk to the height of the menu bar
+            let app: id = msg_send![class!(NSApplication), sharedApplication];
+            let icon: id = msg_send![app, applicationIconImage];
+            let icon: id = msg_send![icon, copy];
+            let _: () = msg_send![icon, setSize: NSSize::new(18.0, 18.0)];
+            let button: id = msg_send![status_item, button];
+            let _: () = msg_send![button, setImage: icon];
+            let _: () = msg_send![icon, release];
+            let _: () = msg_send![button, setToolTip: ns_string(tooltip)];
+
+            let menu = NSMenu::new(nil).autorelease();
+            add_item(menu, "Show Neovide", sel!(show:));
+            add_item(menu, "Quit Neovide", sel!(quit:));
+            let _: () = msg_send![status_item, setMenu: menu];
+
+            status_item
+        }
+    }
+
+    pub fn remove_status_item(status_item: id) {
+        unsafe {
+            let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+            let _: () = msg_send![status_bar, removeStatusItem: status_item];
+            let _: () = msg_send![status_item, release];
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant};
 
 use image::{load_from_memory, GenericImageView, Pixel};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use skulpin::winit;
 use skulpin::winit::event::VirtualKeyCode as Keycode;
 use skulpin::winit::event::{
@@ -21,12 +21,22 @@ use crate::editor::EDITOR;
 use crate::redraw_scheduler::REDRAW_SCHEDULER;
 use crate::renderer::Renderer;
 use crate::settings::*;
+use crate::window::{WindowCommand, WindowError, WINDOW_COMMANDS};
 use crate::INITIAL_DIMENSIONS;
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
 struct Asset;
 
+fn neovim_mouse_button(button: MouseButton) -> Option<&'static str> {
+    match button {
+        MouseButton::Left => Some("left"),
+        MouseButton::Middle => Some("middle"),
+        MouseButton::Right => Some("right"),
+        _ => None,
+    }
+}
+
 #[cfg(target_os = "windows")]
 fn windows_fix_dpi() {
     use winapi::shared::windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
@@ -50,11 +60,12 @@ struct WindowWrapper {
     window: winit::window::Window,
     skulpin_renderer: SkulpinRenderer,
     renderer: Renderer,
-    mouse_down: bool,
+    pressed_buttons: Vec<MouseButton>,
     mouse_position: LogicalPosition<u32>,
     title: String,
     previous_size: PhysicalSize<u32>,
     fullscreen: bool,
+    decorations: bool,
     cached_size: PhysicalSize<u32>,
     cached_position: PhysicalPosition<i32>,
 }
@@ -155,11 +166,12 @@ impl WindowWrapper {
             window: winit_window,
             skulpin_renderer,
             renderer,
-            mouse_down: false,
+            pressed_buttons: Vec::new(),
             mouse_position: saved_position,
             title: String::from("Neovide"),
             previous_size: logical_size.to_physical(scale_factor),
             fullscreen: false,
+            decorations: true,
             cached_size: PhysicalSize {
                 width: 0,
                 height: 0,
@@ -186,6 +198,42 @@ impl WindowWrapper {
         self.fullscreen = !self.fullscreen;
     }
 
+    // Covers the commands winit has an equivalent for. The rest need SDL, so they are logged and
+    // dropped rather than left queued.
+    pub fn handle_window_command(&mut self, command: WindowCommand) {
+        match command {
+            WindowCommand::SetTitle(title) => self.window.set_title(&title),
+            WindowCommand::SetSize(width, height) => {
+                self.window.set_inner_size(LogicalSize { width, height })
+            }
+            WindowCommand::ToggleFullscreen => {
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.fullscreen = !settings.fullscreen;
+                SETTINGS.set(&settings);
+            }
+            WindowCommand::SetTransparency(transparency) => {
+                let mut settings = SETTINGS.get::<WindowSettings>();
+                settings.transparency = transparency;
+                SETTINGS.set(&settings);
+            }
+            WindowCommand::Maximize => self.window.set_maximized(true),
+            WindowCommand::Minimize => self.window.set_minimized(true),
+            WindowCommand::Restore => {
+                self.window.set_minimized(false);
+                self.window.set_maximized(false);
+            }
+            WindowCommand::SetDecorations(decorations) => {
+                self.decorations = decorations;
+                self.window.set_decorations(decorations);
+            }
+            WindowCommand::ToggleDecorations => {
+                self.decorations = !self.decorations;
+                self.window.set_decorations(self.decorations);
+            }
+            command => warn!("{:?} is not supported by the winit backend", command),
+        }
+    }
+
     pub fn synchronize_settings(&mut self) {
         let editor_title = { EDITOR.lock().title.clone() };
 
@@ -202,7 +250,11 @@ impl WindowWrapper {
     }
 
     pub fn handle_quit(&mut self) {
-        BRIDGE.queue_command(UiCommand::Quit);
+        if SETTINGS.get::<WindowSettings>().confirm_quit {
+            BRIDGE.queue_command(UiCommand::CloseRequested);
+        } else {
+            BRIDGE.queue_command(UiCommand::Quit);
+        }
     }
 
     pub fn handle_keyboard_input(
@@ -214,7 +266,8 @@ impl WindowWrapper {
             trace!("Keyboard Input Received: keycode-{:?}", keycode);
         }
 
-        if let Some(keybinding_string) = produce_neovim_keybinding_string(keycode, None, modifiers)
+        if let Some(keybinding_string) =
+            produce_neovim_keybinding_string(keycode, None, modifiers.into())
         {
             BRIDGE.queue_command(UiCommand::Keyboard(keybinding_string));
         }
@@ -229,28 +282,44 @@ impl WindowWrapper {
 
         let winit_window_wrapper = WinitWindow::new(&self.window);
         self.mouse_position = physical_position.to_logical(winit_window_wrapper.scale_factor());
-        if self.mouse_down && previous_position != self.mouse_position {
-            BRIDGE.queue_command(UiCommand::Drag(
-                self.mouse_position.x,
-                self.mouse_position.y,
-            ));
+        // Drags are reported for the earliest button still held
+        if let Some(button) = self
+            .pressed_buttons
+            .first()
+            .copied()
+            .and_then(neovim_mouse_button)
+        {
+            if previous_position != self.mouse_position {
+                BRIDGE.queue_command(UiCommand::Drag {
+                    button: button.to_string(),
+                    position: (self.mouse_position.x, self.mouse_position.y),
+                });
+            }
         }
     }
 
-    pub fn handle_pointer_down(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
-            action: String::from("press"),
-            position: (self.mouse_position.x, self.mouse_position.y),
-        });
-        self.mouse_down = true;
+    pub fn handle_pointer_down(&mut self, button: MouseButton) {
+        if let Some(button_name) = neovim_mouse_button(button) {
+            BRIDGE.queue_command(UiCommand::MouseButton {
+                button: button_name.to_string(),
+                action: String::from("press"),
+                position: (self.mouse_position.x, self.mouse_position.y),
+            });
+        }
+        if !self.pressed_buttons.contains(&button) {
+            self.pressed_buttons.push(button);
+        }
     }
 
-    pub fn handle_pointer_up(&mut self) {
-        BRIDGE.queue_command(UiCommand::MouseButton {
-            action: String::from("release"),
-            position: (self.mouse_position.x, self.mouse_position.y),
-        });
-        self.mouse_down = false;
+    pub fn handle_pointer_up(&mut self, button: MouseButton) {
+        if let Some(button_name) = neovim_mouse_button(button) {
+            BRIDGE.queue_command(UiCommand::MouseButton {
+                button: button_name.to_string(),
+                action: String::from("release"),
+                position: (self.mouse_position.x, self.mouse_position.y),
+            });
+        }
+        self.pressed_buttons.retain(|pressed| *pressed != button);
     }
 
     pub fn handle_mouse_wheel(&mut self, x: f32, y: f32) {
@@ -307,6 +376,8 @@ impl WindowWrapper {
         let current_size = self.previous_size;
 
         if REDRAW_SCHEDULER.should_draw() || SETTINGS.get::<WindowSettings>().no_idle {
+            REDRAW_SCHEDULER
+                .run_animations(1.0 / (SETTINGS.get::<WindowSettings>().refresh_rate as f32));
             let winit_window_wrapper = WinitWindow::new(&self.window);
             let renderer = &mut self.renderer;
             let error = self
@@ -314,7 +385,8 @@ impl WindowWrapper {
                 .draw(&winit_window_wrapper, |canvas, coordinate_system_helper| {
                     let dt = 1.0 / (SETTINGS.get::<WindowSettings>().refresh_rate as f32);
 
-                    if renderer.draw(canvas, &coordinate_system_helper, dt) {
+                    let window_size = coordinate_system_helper.window_logical_size();
+                    if renderer.draw(canvas, window_size, dt) {
                         handle_new_grid_size(current_size.to_logical(scale_factor), &renderer)
                     }
                 })
@@ -335,6 +407,7 @@ struct WindowSettings {
     transparency: f32,
     no_idle: bool,
     fullscreen: bool,
+    confirm_quit: bool,
 }
 
 pub fn initialize_settings() {
@@ -347,20 +420,25 @@ pub fn initialize_settings() {
         transparency: 1.0,
         no_idle,
         fullscreen: false,
+        confirm_quit: false,
     });
 
     register_nvim_setting!("refresh_rate", WindowSettings::refresh_rate);
     register_nvim_setting!("transparency", WindowSettings::transparency);
     register_nvim_setting!("no_idle", WindowSettings::no_idle);
     register_nvim_setting!("fullscreen", WindowSettings::fullscreen);
+    register_nvim_setting!("confirm_quit", WindowSettings::confirm_quit);
 }
 
-pub fn ui_loop() {
+pub fn ui_loop() -> Result<(), WindowError> {
     let event_loop = EventLoop::<()>::with_user_event();
     let mut window = WindowWrapper::new(&event_loop);
     event_loop.run(move |e, _window_target, control_flow| {
         let frame_start = Instant::now();
 
+        for command in WINDOW_COMMANDS.drain() {
+            window.handle_window_command(command);
+        }
         window.synchronize_settings();
 
         let mut keycode = None;
@@ -376,7 +454,11 @@ pub fn ui_loop() {
                 ..
             } => {
                 window.handle_quit();
-                *control_flow = ControlFlow::Exit;
+                // With confirm_quit set, neovim may veto the close, so the loop keeps running
+                // until the bridge reports the process has exited.
+                if !SETTINGS.get::<WindowSettings>().confirm_quit {
+                    *control_flow = ControlFlow::Exit;
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::DroppedFile(path),
@@ -414,18 +496,13 @@ pub fn ui_loop() {
             } => window.handle_mouse_wheel(x, y),
 
             Event::WindowEvent {
-                event:
-                    WindowEvent::MouseInput {
-                        button: MouseButton::Left,
-                        state,
-                        ..
-                    },
+                event: WindowEvent::MouseInput { button, state, .. },
                 ..
             } => {
                 if state == ElementState::Pressed {
-                    window.handle_pointer_down();
+                    window.handle_pointer_down(button);
                 } else {
-                    window.handle_pointer_up();
+                    window.handle_pointer_up(button);
                 }
             }
             Event::WindowEvent {
@@ -0,0 +1,52 @@
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+// SDL only reads the class from the environment, and uses the one name for every part of it. Has
+// to be set before the video subsystem starts, since the Wayland driver reads it once then.
+pub fn set_app_id(app_id: &str) {
+    std::env::set_var("SDL_VIDEO_X11_WMCLASS", app_id);
+    std::env::set_var("SDL_VIDEO_WAYLAND_WMCLASS", app_id);
+}
+
+// Sets an X11 WM_CLASS with distinct instance and class names. Window managers read it when the
+// window is mapped, so this is done while the window is still hidden.
+pub fn set_x11_class(
+    window: &impl HasRawWindowHandle,
+    instance: &str,
+    class: &str,
+) -> Result<(), String> {
+    match window.raw_window_handle() {
+        #[cfg(target_os = "linux")]
+        RawWindowHandle::Xlib(handle) => {
+            x11_set_class_hint(handle.display, handle.window, instance, class)
+        }
+        _ => Err(String::from("WM_CLASS is only used on X11")),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn x11_set_class_hint(
+    display: *mut std::ffi::c_void,
+    window: std::os::raw::c_ulong,
+    instance: &str,
+    class: &str,
+) -> Result<(), String> {
+    use std::ffi::CString;
+    use x11::xlib;
+
+    if display.is_null() {
+        return Err(String::from("No X11 display to set WM_CLASS on"));
+    }
+    let instance = CString::new(instance).map_err(|error| error.to_string())?;
+    let class = CString::new(class).map_err(|error| error.to_string())?;
+
+    unsafe {
+        let display = display as *mut xlib::Display;
+        let mut class_hint = xlib::XClassHint {
+            res_name: instance.as_ptr() as *mut _,
+            res_class: class.as_ptr() as *mut _,
+        };
+        xlib::XSetClassHint(display, window, &mut class_hint);
+        xlib::XFlush(display);
+    }
+    Ok(())
+}